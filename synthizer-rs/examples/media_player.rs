@@ -6,7 +6,8 @@ use std::{env, sync::Mutex};
 use log::Level;
 use shrust::{Shell, ShellIO};
 use synthizer::{
-    Buffer, BufferGenerator, LoggingBackend, Protocol, Source, Source3D, Synthizer, SynthizerError,
+    Buffer, BufferGenerator, Generator, LoggingBackend, Protocol, Source, Source3D, Synthizer,
+    SynthizerError,
 };
 
 struct Data {
@@ -28,18 +29,15 @@ fn main() -> Result<(), SynthizerError> {
             let generator = context.new_buffer_generator()?;
             generator.set_buffer(buffer.clone())?;
             let source = context.new_source3d()?;
-            let data = Data {
-                source: source,
-                generator: generator,
-            };
+            let data = Data { source, generator };
             data.source.add_generator(&data.generator)?;
             let mut shell = Shell::new(Arc::new(data));
             shell.new_command_noargs("play", "Play media.", move |_io, data| {
-                data.source.add_generator(&data.generator)?;
+                data.generator.play()?;
                 Ok(())
             });
             shell.new_command_noargs("pause", "Pause media.", |_io, data| {
-                data.source.remove_generator(&data.generator)?;
+                data.generator.pause()?;
                 Ok(())
             });
             // Track this here because I'm too lazy to implement `DerefMut` on `Data`.
@@ -62,7 +60,7 @@ fn main() -> Result<(), SynthizerError> {
                 1,
                 |io, data, args| {
                     let gain = args[0].parse::<f64>();
-                    if let Some(gain) = gain.ok() {
+                    if let Ok(gain) = gain {
                         let base: f64 = 10.;
                         let gain = base.powf(gain / 20.);
                         writeln!(io, "Setting gain to {} DB", args[0])?;
@@ -75,9 +73,9 @@ fn main() -> Result<(), SynthizerError> {
             );
             shell.new_command("seek", "Seek in seconds.", 1, |io, data, args| {
                 let position = args[0].parse::<f64>();
-                if let Some(position) = position.ok() {
+                if let Ok(position) = position {
                     writeln!(io, "Seeking to {}", args[0])?;
-                    data.generator.set_position(position)?;
+                    data.generator.set_playback_position(position)?;
                 } else {
                     writeln!(io, "{} not a valid position", args[0])?;
                 }
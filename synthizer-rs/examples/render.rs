@@ -0,0 +1,46 @@
+use std::{env, path::Path, time::Duration};
+
+use synthizer::{Buffer, Protocol, Source, Synthizer, SynthizerError, WavFormat};
+
+fn main() -> Result<(), SynthizerError> {
+    let args = env::args().collect::<Vec<String>>();
+    if args.len() < 3 {
+        eprintln!(
+            "Usage: {} <input> <output.wav> [seconds] [x y z] [--float]",
+            env::current_exe().unwrap().to_string_lossy()
+        );
+        return Ok(());
+    }
+    let format = if args.iter().any(|v| v == "--float") {
+        WavFormat::Float32
+    } else {
+        WavFormat::Int16
+    };
+    let numbers = args[3..]
+        .iter()
+        .filter_map(|v| v.parse::<f64>().ok())
+        .collect::<Vec<f64>>();
+    let synthizer = Synthizer::new()?;
+    let mut context = synthizer.new_headless_context()?;
+    let buffer = Buffer::new(Protocol::File, Path::new(&args[1]), "")?;
+    let seconds = match numbers.first() {
+        Some(seconds) => *seconds,
+        None => buffer.get_length_in_seconds()?,
+    };
+    let generator = context.new_buffer_generator()?;
+    generator.set_buffer(buffer)?;
+    let source = context.new_source3d()?;
+    if let [_, x, y, z, ..] = numbers[..] {
+        source.set_position(x, y, z)?;
+    }
+    source.add_generator(&generator)?;
+    let duration = match Duration::try_from_secs_f64(seconds) {
+        Ok(duration) => duration,
+        Err(e) => {
+            eprintln!("Can't render for {} seconds: {}", seconds, e);
+            return Ok(());
+        }
+    };
+    context.render_to_wav(&args[2], duration, format)?;
+    Ok(())
+}
@@ -0,0 +1,288 @@
+//! Scheduling property changes on the audio thread.
+//!
+//! Changing a property from the game loop takes effect at the start of the
+//! next block Synthizer renders, which can be heard as zipper noise or
+//! jitter. Automation instead schedules changes at exact times on the
+//! context's clock, which the audio thread follows sample by sample.
+//!
+//! Changes are collected in an [`AutomationBatch`], then applied at once with
+//! [`AutomationBatch::execute`]. Times are in seconds on the context's clock,
+//! from [`Context::get_current_time`](crate::Context::get_current_time).
+//! Each point says how to get from the point before it, so a ramp is a
+//! [`set`](AutomationBatch::set) to its start followed by a
+//! [`linear_ramp_to`](AutomationBatch::linear_ramp_to) its end.
+
+use std::ptr::null_mut;
+
+use crate::{
+    context::BLOCK_SIZE, ffi::*, orientation::values_to_synthizer, Automatable, Context, Handle,
+    Object, Property, SynthizerError, SAMPLE_RATE,
+};
+
+// The most linear segments an exponential ramp is split into.
+const MAX_SEGMENTS: usize = 1024;
+
+/// Lets a bare handle be automated.
+pub(crate) struct Target<'a>(pub(crate) &'a Handle);
+
+impl Object for Target<'_> {
+    fn handle(&self) -> &Handle {
+        self.0
+    }
+}
+
+/// A set of automation commands, applied together by
+/// [`execute`](AutomationBatch::execute).
+///
+/// Created by [`Context::new_automation_batch`](crate::Context::new_automation_batch).
+/// Commands are checked as they're added, and objects must belong to the
+/// batch's context.
+#[derive(Debug)]
+pub struct AutomationBatch(pub(crate) Handle);
+
+impl AutomationBatch {
+    pub(crate) fn new(context: &Context) -> Result<Self, SynthizerError> {
+        let mut handle = 0;
+        wrap!(
+            unsafe { syz_createAutomationBatch(&mut handle, **context, null_mut(), None) },
+            Self(Handle::new_in(handle, context.handle()))
+        )
+    }
+
+    fn add(&mut self, commands: &[syz_AutomationCommand]) -> Result<&mut Self, SynthizerError> {
+        wrap!(
+            unsafe {
+                syz_automationBatchAddCommands(*self.0, commands.len() as u64, commands.as_ptr())
+            },
+            self
+        )
+    }
+
+    fn command(target: &impl Object, time: f64, kind: u32) -> syz_AutomationCommand {
+        let mut command = unsafe { std::mem::zeroed::<syz_AutomationCommand>() };
+        command.target = **target.handle();
+        command.time = time;
+        command.type_ = kind as i32;
+        command
+    }
+
+    fn point<T: Automatable>(
+        target: &impl Object,
+        property: Property<T>,
+        time: f64,
+        value: T,
+        interpolation: u32,
+    ) -> syz_AutomationCommand {
+        let mut command = Self::command(
+            target,
+            time,
+            SYZ_AUTOMATION_COMMANDS_SYZ_AUTOMATION_COMMAND_APPEND_PROPERTY as u32,
+        );
+        command.params.append_to_property = syz_AutomationAppendPropertyCommand {
+            property: property.id(),
+            point: syz_AutomationPoint {
+                interpolation_type: interpolation as i32,
+                values: values_to_synthizer(target.handle(), property.id(), value.to_values()),
+                flags: 0,
+            },
+        };
+        command
+    }
+
+    /// Jumps `property` to `value` at `time`.
+    pub fn set<T: Automatable>(
+        &mut self,
+        target: &impl Object,
+        property: Property<T>,
+        time: f64,
+        value: T,
+    ) -> Result<&mut Self, SynthizerError> {
+        let interpolation = SYZ_INTERPOLATION_TYPES_SYZ_INTERPOLATION_TYPE_NONE as u32;
+        self.add(&[Self::point(target, property, time, value, interpolation)])
+    }
+
+    /// Moves `property` in a straight line from the previous point, reaching
+    /// `value` at `time`.
+    pub fn linear_ramp_to<T: Automatable>(
+        &mut self,
+        target: &impl Object,
+        property: Property<T>,
+        time: f64,
+        value: T,
+    ) -> Result<&mut Self, SynthizerError> {
+        let interpolation = SYZ_INTERPOLATION_TYPES_SYZ_INTERPOLATION_TYPE_LINEAR as u32;
+        self.add(&[Self::point(target, property, time, value, interpolation)])
+    }
+
+    /// Moves `property` from `from` at `start` to `to` at `end` along an
+    /// exponential curve, which sounds even for gains and frequencies.
+    ///
+    /// `from` and `to` must both be positive or both negative. Synthizer only
+    /// interpolates linearly, so the curve is made of one linear segment per
+    /// block.
+    pub fn exponential_ramp(
+        &mut self,
+        target: &impl Object,
+        property: Property<f64>,
+        (start, from): (f64, f64),
+        (end, to): (f64, f64),
+    ) -> Result<&mut Self, SynthizerError> {
+        // Written so NaNs fail too.
+        let valid = from * to > 0. && end >= start;
+        if !valid {
+            return Err(SynthizerError::InvalidArgument(format!(
+                "Can't ramp exponentially from {} at {} to {} at {}",
+                from, start, to, end
+            )));
+        }
+        let block = BLOCK_SIZE as f64 / SAMPLE_RATE as f64;
+        let segments = (((end - start) / block).ceil() as usize).clamp(1, MAX_SEGMENTS);
+        let linear = SYZ_INTERPOLATION_TYPES_SYZ_INTERPOLATION_TYPE_LINEAR as u32;
+        let none = SYZ_INTERPOLATION_TYPES_SYZ_INTERPOLATION_TYPE_NONE as u32;
+        let mut commands = vec![Self::point(target, property, start, from, none)];
+        commands.extend((1..=segments).map(|i| {
+            let t = i as f64 / segments as f64;
+            let value = from * (to / from).powf(t);
+            Self::point(target, property, start + (end - start) * t, value, linear)
+        }));
+        self.add(&commands)
+    }
+
+    /// Drops all automation of `property` not yet reached, leaving it where
+    /// it is.
+    pub fn clear<T: Automatable>(
+        &mut self,
+        target: &impl Object,
+        property: Property<T>,
+    ) -> Result<&mut Self, SynthizerError> {
+        let mut command = Self::command(
+            target,
+            0.,
+            SYZ_AUTOMATION_COMMANDS_SYZ_AUTOMATION_COMMAND_CLEAR_PROPERTY as u32,
+        );
+        command.params.clear_property = syz_AutomationClearPropertyCommand {
+            property: property.id(),
+        };
+        self.add(&[command])
+    }
+
+    /// Drops all pending automation of every property of `target`.
+    pub fn clear_all(&mut self, target: &impl Object) -> Result<&mut Self, SynthizerError> {
+        let kind = SYZ_AUTOMATION_COMMANDS_SYZ_AUTOMATION_COMMAND_CLEAR_ALL_PROPERTIES as u32;
+        self.add(&[Self::command(target, 0., kind)])
+    }
+
+    /// Has `target` send an
+    /// [`EventKind::UserAutomation`](crate::EventKind::UserAutomation) event
+    /// with `param` at `time`.
+    pub fn send_user_event(
+        &mut self,
+        target: &impl Object,
+        time: f64,
+        param: u64,
+    ) -> Result<&mut Self, SynthizerError> {
+        let mut command = Self::command(
+            target,
+            time,
+            SYZ_AUTOMATION_COMMANDS_SYZ_AUTOMATION_COMMAND_SEND_USER_EVENT as u32,
+        );
+        command.params.send_user_event = syz_AutomationSendUserEventCommand { param };
+        self.add(&[command])
+    }
+
+    /// Cancels user events `target` hasn't sent yet.
+    pub fn clear_events(&mut self, target: &impl Object) -> Result<&mut Self, SynthizerError> {
+        let kind = SYZ_AUTOMATION_COMMANDS_SYZ_AUTOMATION_COMMAND_CLEAR_EVENTS as u32;
+        self.add(&[Self::command(target, 0., kind)])
+    }
+
+    /// Sends every command to the audio thread, which applies them together
+    /// before rendering its next block.
+    pub fn execute(self) -> Result<(), SynthizerError> {
+        wrap!(unsafe { syz_automationBatchExecute(*self.0) })
+    }
+}
+
+impl Context {
+    pub fn new_automation_batch(&self) -> Result<AutomationBatch, SynthizerError> {
+        AutomationBatch::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock, property, Synthizer};
+
+    #[test]
+    fn schedules_points() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        let mut batch = context.new_automation_batch()?;
+        batch
+            .set(&source, property::GAIN, 1., 0.)?
+            .linear_ramp_to(&source, property::GAIN, 2., 1.)?
+            .linear_ramp_to(&source, property::POSITION, 2., (1., 2., 3.))?;
+        assert!(mock::automation(**source.handle(), property::GAIN.id()).is_empty());
+        batch.execute()?;
+        let none = SYZ_INTERPOLATION_TYPES_SYZ_INTERPOLATION_TYPE_NONE as i32;
+        let linear = SYZ_INTERPOLATION_TYPES_SYZ_INTERPOLATION_TYPE_LINEAR as i32;
+        assert_eq!(
+            mock::automation(**source.handle(), property::GAIN.id()),
+            [(1., none, [0.; 6]), (2., linear, [1., 0., 0., 0., 0., 0.])]
+        );
+        assert_eq!(
+            mock::automation(**source.handle(), property::POSITION.id()),
+            [(2., linear, [1., 2., 3., 0., 0., 0.])]
+        );
+        let mut batch = context.new_automation_batch()?;
+        batch.clear(&source, property::GAIN)?;
+        batch.execute()?;
+        assert!(mock::automation(**source.handle(), property::GAIN.id()).is_empty());
+        assert!(!mock::automation(**source.handle(), property::POSITION.id()).is_empty());
+        let mut batch = context.new_automation_batch()?;
+        batch.clear_all(&source)?;
+        batch.execute()?;
+        assert!(mock::automation(**source.handle(), property::POSITION.id()).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn ramps_exponentially() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let generator = context.new_buffer_generator()?;
+        let mut batch = context.new_automation_batch()?;
+        batch.exponential_ramp(&generator, property::PITCH_BEND, (0., 1.), (1., 4.))?;
+        batch.execute()?;
+        let points = mock::automation(**generator.handle(), property::PITCH_BEND.id());
+        let (first, last) = (points[0], points[points.len() - 1]);
+        assert_eq!((first.0, first.2[0]), (0., 1.));
+        assert_eq!(last.0, 1.);
+        assert!((last.2[0] - 4.).abs() < 1e-9);
+        let middle = points[points.len() / 2];
+        assert!((middle.0 - 0.5).abs() < 0.01);
+        assert!((middle.2[0] - 2.).abs() < 0.05);
+        let mut batch = context.new_automation_batch()?;
+        for (from, to) in [(0., 1.), (-1., 1.), (1., f64::NAN)].iter() {
+            let error = batch
+                .exponential_ramp(&generator, property::PITCH_BEND, (0., *from), (1., *to))
+                .unwrap_err();
+            assert!(matches!(error, SynthizerError::InvalidArgument(_)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn tracks_time() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        assert_eq!(context.get_current_time()?, 0.);
+        context.render_frames(BLOCK_SIZE * 2)?;
+        let expected = (BLOCK_SIZE * 2) as f64 / SAMPLE_RATE as f64;
+        assert!((context.get_current_time()? - expected).abs() < 1e-9);
+        assert!(context.get_suggested_automation_time()? > context.get_current_time()?);
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use std::{ffi::CString, os::raw::c_char, path::Path, ptr::null_mut, time::Duration};
+
+use crate::{ffi::*, Handle, Library, Object, SynthizerError};
+
+pub enum Protocol {
+    File,
+    /// A protocol registered with `Synthizer::register_stream_protocol`.
+    Custom(String),
+}
+
+impl Protocol {
+    pub(crate) fn to_cstring(&self) -> Result<CString, SynthizerError> {
+        let protocol = match self {
+            Protocol::File => "file",
+            Protocol::Custom(protocol) => protocol,
+        };
+        Ok(CString::new(protocol)?)
+    }
+}
+
+pub(crate) fn path_to_cstring(path: &Path) -> Result<CString, SynthizerError> {
+    let path = path.as_os_str().to_string_lossy();
+    Ok(CString::new(path.as_bytes())?)
+}
+
+#[derive(Clone, Debug)]
+pub struct Buffer(pub(crate) Handle);
+
+impl Buffer {
+    /// Decodes a buffer from a stream.
+    ///
+    /// Synthizer 0.11 no longer takes stream options, so `options` is
+    /// ignored. It's kept so code written against 0.7 still compiles.
+    pub fn new<S: Into<String>>(
+        protocol: Protocol,
+        path: &Path,
+        _options: S,
+    ) -> Result<Self, SynthizerError> {
+        let library = Library::current()?;
+        let mut handle = 0;
+        let protocol = protocol.to_cstring()?;
+        let path = path_to_cstring(path)?;
+        wrap!(
+            unsafe {
+                syz_createBufferFromStreamParams(
+                    &mut handle,
+                    protocol.as_ptr(),
+                    path.as_ptr(),
+                    null_mut(),
+                    null_mut(),
+                    None,
+                )
+            },
+            Self(Handle::new(handle, library))
+        )
+    }
+
+    /// Decodes a buffer from encoded audio in memory, in any format Synthizer
+    /// can read from a file.
+    ///
+    /// Decoding finishes before this returns, so `data` only needs to live
+    /// for the duration of the call.
+    pub fn from_encoded_bytes(data: &[u8]) -> Result<Self, SynthizerError> {
+        let library = Library::current()?;
+        let mut handle = 0;
+        wrap!(
+            unsafe {
+                syz_createBufferFromEncodedData(
+                    &mut handle,
+                    data.len() as u64,
+                    data.as_ptr() as *const c_char,
+                    null_mut(),
+                    None,
+                )
+            },
+            Self(Handle::new(handle, library))
+        )
+    }
+
+    /// Like `from_encoded_bytes`, but takes ownership of the data, e.g. a
+    /// `Vec<u8>` or `Arc<[u8]>`, which is dropped once decoding is done.
+    pub fn from_encoded_data(data: impl AsRef<[u8]>) -> Result<Self, SynthizerError> {
+        Self::from_encoded_bytes(data.as_ref())
+    }
+
+    /// Creates a buffer from interleaved samples, with `samples.len()` a
+    /// multiple of `channels`. The samples are copied, so they only need to
+    /// live for the duration of the call.
+    pub fn from_float_samples(
+        sample_rate: u32,
+        channels: u32,
+        samples: &[f32],
+    ) -> Result<Self, SynthizerError> {
+        if channels == 0 || samples.len() % channels as usize != 0 {
+            return Err(SynthizerError::InvalidArgument(format!(
+                "{} samples can't be split into {} channels",
+                samples.len(),
+                channels
+            )));
+        }
+        let frames = (samples.len() / channels as usize) as u64;
+        let library = Library::current()?;
+        let mut handle = 0;
+        wrap!(
+            unsafe {
+                syz_createBufferFromFloatArray(
+                    &mut handle,
+                    sample_rate,
+                    channels,
+                    frames,
+                    samples.as_ptr(),
+                    null_mut(),
+                    None,
+                )
+            },
+            Self(Handle::new(handle, library))
+        )
+    }
+
+    /// Like `from_float_samples`, but for 16-bit samples.
+    pub fn from_i16_samples(
+        sample_rate: u32,
+        channels: u32,
+        samples: &[i16],
+    ) -> Result<Self, SynthizerError> {
+        let samples: Vec<f32> = samples.iter().map(|v| *v as f32 / 32768.).collect();
+        Self::from_float_samples(sample_rate, channels, &samples)
+    }
+
+    pub fn get_channels(&self) -> Result<u32, SynthizerError> {
+        let mut out = 0;
+        wrap!(
+            unsafe { syz_bufferGetChannels(&mut out, **self.handle()) },
+            out
+        )
+    }
+
+    pub fn get_length_in_samples(&self) -> Result<u32, SynthizerError> {
+        let mut out = 0;
+        wrap!(
+            unsafe { syz_bufferGetLengthInSamples(&mut out, **self.handle()) },
+            out
+        )
+    }
+
+    pub fn get_length_in_seconds(&self) -> Result<f64, SynthizerError> {
+        let mut out = 0.;
+        wrap!(
+            unsafe { syz_bufferGetLengthInSeconds(&mut out, **self.handle()) },
+            out
+        )
+    }
+
+    pub fn get_duration(&self) -> Result<Duration, SynthizerError> {
+        let seconds = self.get_length_in_seconds()?;
+        Ok(Duration::from_secs_f64(seconds))
+    }
+}
+
+make_object!(Buffer);
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{mock, Synthizer};
+
+    #[test]
+    fn decodes_borrowed_bytes() -> Result<(), SynthizerError> {
+        let _synthizer = Synthizer::new()?;
+        let buffer = Buffer::from_encoded_bytes(mock::WAV)?;
+        assert_eq!(buffer.get_channels()?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn decodes_owned_bytes() -> Result<(), SynthizerError> {
+        let _synthizer = Synthizer::new()?;
+        let data: Vec<u8> = mock::WAV.into();
+        Buffer::from_encoded_data(data)?;
+        let data: Arc<[u8]> = mock::WAV.into();
+        Buffer::from_encoded_data(data)?;
+        Ok(())
+    }
+
+    #[test]
+    fn creates_from_samples() -> Result<(), SynthizerError> {
+        let _synthizer = Synthizer::new()?;
+        let samples = [0.5, -0.5, 0.25, -0.25, 1., -1., 0., 0.];
+        let buffer = Buffer::from_float_samples(22050, 2, &samples)?;
+        let received = mock::buffer_samples(**buffer.handle()).unwrap();
+        assert_eq!(received, (22050, 2, samples.to_vec()));
+        assert_eq!(buffer.get_channels()?, 2);
+        assert_eq!(buffer.get_length_in_samples()?, 4);
+        let samples = [i16::MIN, -16384, 0, 16384, i16::MAX];
+        let buffer = Buffer::from_i16_samples(44100, 1, &samples)?;
+        let (sample_rate, channels, received) = mock::buffer_samples(**buffer.handle()).unwrap();
+        assert_eq!((sample_rate, channels), (44100, 1));
+        assert_eq!(received[..4], [-1., -0.5, 0., 0.5]);
+        assert_eq!(received[4], 32767. / 32768.);
+        assert_eq!(buffer.get_length_in_samples()?, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_partial_frames() -> Result<(), SynthizerError> {
+        let _synthizer = Synthizer::new()?;
+        let samples = [0.; 3];
+        let error = Buffer::from_float_samples(44100, 2, &samples).unwrap_err();
+        assert!(matches!(error, SynthizerError::InvalidArgument(_)));
+        let error = Buffer::from_i16_samples(44100, 0, &[]).unwrap_err();
+        assert!(matches!(error, SynthizerError::InvalidArgument(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_formats_fail_to_decode() -> Result<(), SynthizerError> {
+        let _synthizer = Synthizer::new()?;
+        let error = Buffer::from_encoded_bytes(b"not audio").unwrap_err();
+        assert!(matches!(error, SynthizerError::Decoding(_)));
+        Ok(())
+    }
+}
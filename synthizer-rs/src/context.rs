@@ -0,0 +1,492 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    ptr::null_mut,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    events::{self, Event, EventReceiver, Events},
+    ffi::*,
+    property, wav, AngularPannedSource, BufferGenerator, DirectSource, DistanceModel,
+    FastSineBankGenerator, Handle, Library, NoiseGenerator, Object, PannerStrategy, Protocol,
+    ScalarPannedSource, SineBankWave, Source3D, StreamingGenerator, SynthizerError, WavFormat,
+    Waveform,
+};
+
+#[derive(Clone, Debug)]
+pub struct Context(pub(crate) Handle);
+
+impl Context {
+    pub(crate) fn new(library: Arc<Library>) -> Result<Self, SynthizerError> {
+        let mut handle = 0;
+        wrap!(
+            unsafe { syz_createContext(&mut handle, null_mut(), None) },
+            Self(Handle::new(handle, library))
+        )
+    }
+
+    /// Streams audio from `path`.
+    ///
+    /// Synthizer 0.11 no longer takes stream options, so `options` is
+    /// ignored. It's kept so code written against 0.7 still compiles.
+    pub fn new_streaming_generator<S: Into<String>>(
+        &mut self,
+        protocol: Protocol,
+        path: &Path,
+        options: S,
+    ) -> Result<StreamingGenerator, SynthizerError> {
+        StreamingGenerator::new(self, protocol, path, options)
+    }
+
+    pub fn new_buffer_generator(&mut self) -> Result<BufferGenerator, SynthizerError> {
+        BufferGenerator::new(self)
+    }
+
+    pub fn new_noise_generator(&mut self, channels: u32) -> Result<NoiseGenerator, SynthizerError> {
+        NoiseGenerator::new(self, channels)
+    }
+
+    pub fn new_fast_sine_bank_generator(
+        &mut self,
+        initial_frequency: f64,
+        waves: &[SineBankWave],
+    ) -> Result<FastSineBankGenerator, SynthizerError> {
+        FastSineBankGenerator::new(self, initial_frequency, waves)
+    }
+
+    /// Creates a sine bank generator playing one of Synthizer's built-in
+    /// waveforms.
+    pub fn new_waveform_generator(
+        &mut self,
+        waveform: Waveform,
+        initial_frequency: f64,
+    ) -> Result<FastSineBankGenerator, SynthizerError> {
+        FastSineBankGenerator::from_waveform(self, waveform, initial_frequency)
+    }
+
+    pub fn new_direct_source(&mut self) -> Result<DirectSource, SynthizerError> {
+        DirectSource::new(self)
+    }
+
+    pub fn new_angular_panned_source(
+        &mut self,
+        panner_strategy: PannerStrategy,
+        azimuth: f64,
+        elevation: f64,
+    ) -> Result<AngularPannedSource, SynthizerError> {
+        AngularPannedSource::new(self, panner_strategy, azimuth, elevation)
+    }
+
+    pub fn new_scalar_panned_source(
+        &mut self,
+        panner_strategy: PannerStrategy,
+        panning_scalar: f64,
+    ) -> Result<ScalarPannedSource, SynthizerError> {
+        ScalarPannedSource::new(self, panner_strategy, panning_scalar)
+    }
+
+    pub fn new_source3d(&mut self) -> Result<Source3D, SynthizerError> {
+        Source3D::new(self, PannerStrategy::Delegate, 0., 0., 0.)
+    }
+
+    /// Pauses all audio output from this context.
+    pub fn pause(&self) -> Result<(), SynthizerError> {
+        self.handle().pause()
+    }
+
+    /// Resumes a paused context.
+    pub fn play(&self) -> Result<(), SynthizerError> {
+        self.handle().play()
+    }
+
+    /// Whether the context is playing rather than paused.
+    pub fn is_playing(&self) -> bool {
+        self.handle().is_playing()
+    }
+
+    /// Starts queueing events for this context.
+    ///
+    /// Queued events must be drained, either with `events` or from a
+    /// background thread, or they'll pile up forever.
+    pub fn enable_events(&self) -> Result<(), SynthizerError> {
+        wrap!(unsafe { syz_contextEnableEvents(**self.handle()) })
+    }
+
+    /// Drains the events queued so far.
+    ///
+    /// Events only go to one place, so this sees nothing taken by
+    /// `event_receiver`, `on_event` or an async consumer.
+    pub fn events(&self) -> Events<'_> {
+        Events::new(self)
+    }
+
+    /// Drains events every `interval` from a background thread, sending them
+    /// to the returned receiver.
+    ///
+    /// The thread exits at its next poll once the receiver or every clone of
+    /// this context is dropped. Events only go to one place, so don't combine
+    /// this with `events`, `on_event` or an async consumer.
+    pub fn event_receiver(&self, interval: Duration) -> EventReceiver {
+        events::receiver(self, interval)
+    }
+
+    /// Drains events every `interval` from a background thread, calling
+    /// `callback` with each until every clone of this context is dropped.
+    ///
+    /// Events only go to one place, so don't combine this with `events`,
+    /// `event_receiver` or an async consumer.
+    pub fn on_event(&self, interval: Duration, mut callback: impl FnMut(Event) + Send + 'static) {
+        events::spawn_poller(
+            self,
+            interval,
+            || false,
+            move |event| {
+                callback(event);
+                true
+            },
+        )
+    }
+
+    /// The time in seconds on the context's clock, which automation is
+    /// scheduled against.
+    pub fn get_current_time(&self) -> Result<f64, SynthizerError> {
+        self.handle().get(property::CURRENT_TIME)
+    }
+
+    /// A time far enough ahead of the audio thread that automation scheduled
+    /// for it won't be late.
+    pub fn get_suggested_automation_time(&self) -> Result<f64, SynthizerError> {
+        self.handle().get(property::SUGGESTED_AUTOMATION_TIME)
+    }
+
+    property!(pub gain: f64 = property::GAIN);
+    d3!(pub position = property::POSITION);
+    d6!(pub orientation = property::ORIENTATION);
+    property!(pub default_panner_strategy: PannerStrategy = property::DEFAULT_PANNER_STRATEGY);
+    property!(pub default_distance_model: DistanceModel = property::DEFAULT_DISTANCE_MODEL);
+    property!(pub default_distance_ref: f64 = property::DEFAULT_DISTANCE_REF);
+    property!(pub default_distance_max: f64 = property::DEFAULT_DISTANCE_MAX);
+    property!(pub default_rolloff: f64 = property::DEFAULT_ROLLOFF);
+    property!(pub default_closeness_boost: f64 = property::DEFAULT_CLOSENESS_BOOST);
+    property!(
+        pub default_closeness_boost_distance: f64 = property::DEFAULT_CLOSENESS_BOOST_DISTANCE
+    );
+}
+
+impl std::ops::Deref for Context {
+    type Target = syz_Handle;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+make_object!(Context);
+
+/// The sample rate Synthizer renders at.
+pub const SAMPLE_RATE: u32 = 44100;
+
+// Frames in each block Synthizer renders.
+pub(crate) const BLOCK_SIZE: usize = 256;
+
+/// A context which renders audio on demand instead of playing it.
+///
+/// Nothing is heard until `render` is called, and then only as much audio as
+/// was asked for, so the output is the same no matter how fast the machine
+/// is. Everything a `Context` can do is available through `Deref`.
+#[derive(Debug)]
+pub struct HeadlessContext {
+    context: Context,
+    // Rendered samples which didn't fit in the last call to `render`.
+    pending: Vec<f32>,
+}
+
+fn output(e: io::Error) -> SynthizerError {
+    SynthizerError::Output(Arc::new(e))
+}
+
+impl HeadlessContext {
+    pub(crate) fn new(library: Arc<Library>) -> Result<Self, SynthizerError> {
+        let mut handle = 0;
+        wrap!(
+            unsafe { syz_createContextHeadless(&mut handle, null_mut(), None) },
+            Self {
+                context: Context(Handle::new(handle, library)),
+                pending: Vec::with_capacity(BLOCK_SIZE * 2),
+            }
+        )
+    }
+
+    /// Renders `output.len() / 2` frames of interleaved stereo audio.
+    pub fn render(&mut self, output: &mut [f32]) -> Result<(), SynthizerError> {
+        if output.len() % 2 != 0 {
+            return Err(SynthizerError::InvalidArgument(format!(
+                "{} samples aren't a whole number of stereo frames",
+                output.len()
+            )));
+        }
+        let mut written = self.pending.len().min(output.len());
+        output[..written].copy_from_slice(&self.pending[..written]);
+        self.pending.drain(..written);
+        let mut block = [0.; BLOCK_SIZE * 2];
+        while written < output.len() {
+            wrap!(unsafe { syz_contextGetBlock(**self.context.handle(), block.as_mut_ptr()) })?;
+            let n = block.len().min(output.len() - written);
+            output[written..written + n].copy_from_slice(&block[..n]);
+            self.pending.extend_from_slice(&block[n..]);
+            written += n;
+        }
+        Ok(())
+    }
+
+    /// Renders `frames` frames of interleaved stereo audio into a new
+    /// vector.
+    pub fn render_frames(&mut self, frames: usize) -> Result<Vec<f32>, SynthizerError> {
+        let mut output = vec![0.; frames * 2];
+        self.render(&mut output)?;
+        Ok(output)
+    }
+
+    /// Renders `duration` of audio to a stereo WAV file at `path`.
+    pub fn render_to_wav(
+        &mut self,
+        path: impl AsRef<Path>,
+        duration: Duration,
+        format: WavFormat,
+    ) -> Result<(), SynthizerError> {
+        let frames = (duration.as_secs_f64() * SAMPLE_RATE as f64).round();
+        // Checked before creating the file, so nothing is left behind.
+        if frames > wav::max_frames(format, 2)? as f64 {
+            return Err(SynthizerError::InvalidArgument(format!(
+                "{:?} is too long for a WAV file",
+                duration
+            )));
+        }
+        let frames = frames as u32;
+        let mut out = BufWriter::new(File::create(path).map_err(output)?);
+        wav::write_header(&mut out, format, SAMPLE_RATE, 2, frames)?;
+        let mut block = [0.; BLOCK_SIZE * 2];
+        let mut remaining = frames as usize;
+        while remaining > 0 {
+            let n = remaining.min(BLOCK_SIZE);
+            self.render(&mut block[..n * 2])?;
+            wav::write_samples(&mut out, format, &block[..n * 2]).map_err(output)?;
+            remaining -= n;
+        }
+        out.flush().map_err(output)?;
+        Ok(())
+    }
+}
+
+impl std::ops::Deref for HeadlessContext {
+    type Target = Context;
+
+    fn deref(&self) -> &Self::Target {
+        &self.context
+    }
+}
+
+impl std::ops::DerefMut for HeadlessContext {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.context
+    }
+}
+
+impl Object for HeadlessContext {
+    fn handle(&self) -> &Handle {
+        self.context.handle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock, EventKind, EventSource, Generator, Source, Synthizer};
+
+    #[test]
+    fn renders_arbitrary_frame_counts() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let mut rendered = context.render_frames(100)?;
+        rendered.extend(context.render_frames(300)?);
+        let mut output = [0.; 200];
+        context.render(&mut output)?;
+        rendered.extend_from_slice(&output);
+        // The mock numbers every sample it renders.
+        for (i, sample) in rendered.iter().enumerate() {
+            assert_eq!(*sample, i as f32);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn renders_to_wav() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let path = std::env::temp_dir().join(format!("synthizer-{}.wav", std::process::id()));
+        context.render_to_wav(&path, Duration::from_millis(10), WavFormat::Float32)?;
+        let data = std::fs::read(&path).map_err(output)?;
+        std::fs::remove_file(&path).map_err(output)?;
+        // 441 stereo frames of 4-byte samples, after the header.
+        assert_eq!(data.len(), 44 + 441 * 2 * 4);
+        assert_eq!(&data[..4], b"RIFF");
+        assert_eq!(&data[48..52], &1f32.to_le_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_renders_too_long_for_wav() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let path = std::env::temp_dir().join(format!("synthizer-long-{}.wav", std::process::id()));
+        // Over the 4 GB limit as 16-bit stereo, though it fits in u32 frames.
+        let duration = Duration::from_secs(7 * 60 * 60);
+        let error = context
+            .render_to_wav(&path, duration, WavFormat::Int16)
+            .unwrap_err();
+        assert!(matches!(error, SynthizerError::InvalidArgument(_)));
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn events_reference_their_source() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        context.enable_events()?;
+        let generator = context.new_buffer_generator()?;
+        generator.set_looping(true)?;
+        let raw = **generator.handle();
+        mock::push_event(
+            **context.handle(),
+            SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_LOOPED,
+            raw,
+            0,
+        );
+        mock::push_event(
+            **context.handle(),
+            SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_FINISHED,
+            raw,
+            0,
+        );
+        let events = context.events().collect::<Result<Vec<Event>, _>>()?;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, EventKind::Looped);
+        assert_eq!(events[1].kind, EventKind::Finished);
+        match &events[0].source {
+            EventSource::BufferGenerator(v) => assert!(v.get_looping()?),
+            v => panic!("Wrong source: {:?}", v),
+        }
+        drop(events);
+        assert_eq!(mock::refcount(raw), Some(1));
+        assert!(context.events().next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn events_can_be_received_from_a_thread() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        context.enable_events()?;
+        let source = context.new_direct_source()?;
+        let receiver = context.event_receiver(Duration::from_millis(1));
+        mock::push_event(
+            **context.handle(),
+            SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_FINISHED,
+            **source.handle(),
+            0,
+        );
+        let event = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(event.source, EventSource::DirectSource(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn pollers_stop_without_waiting_for_an_event() -> Result<(), SynthizerError> {
+        // Tells the test when the poller's closures are dropped, as its thread
+        // exits.
+        struct Stopped(std::sync::mpsc::Sender<()>);
+
+        impl Drop for Stopped {
+            fn drop(&mut self) {
+                self.0.send(()).ok();
+            }
+        }
+
+        let synthizer = Synthizer::new()?;
+        let context = synthizer.new_headless_context()?;
+        context.enable_events()?;
+        let (sender, stopped) = std::sync::mpsc::channel();
+        let guard = Stopped(sender);
+        let alive = Arc::new(());
+        let watched = Arc::downgrade(&alive);
+        let done = move || {
+            let _ = &guard;
+            watched.strong_count() == 0
+        };
+        events::spawn_poller(&context, Duration::from_millis(1), done, |_| true);
+        drop(alive);
+        stopped.recv_timeout(Duration::from_secs(5)).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn events_end_after_an_error() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        context.enable_events()?;
+        let source = context.new_direct_source()?;
+        let finished = SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_FINISHED;
+        mock::push_event(**context.handle(), finished, u64::MAX, 0);
+        mock::push_event(**context.handle(), finished, **source.handle(), 0);
+        let mut events = context.events();
+        assert!(matches!(events.next(), Some(Err(_))));
+        assert!(events.next().is_none());
+        // The rest are still there for next time.
+        let events = context.events().collect::<Result<Vec<Event>, _>>()?;
+        assert_eq!(events.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn pausing_is_shared_between_clones() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let source = context.new_direct_source()?;
+        let generator = context.new_buffer_generator()?;
+        assert!(context.is_playing() && source.is_playing() && generator.is_playing());
+        let clone = generator.clone();
+        generator.pause()?;
+        assert!(!clone.is_playing());
+        assert!(mock::is_paused(**generator.handle()));
+        clone.play()?;
+        assert!(generator.is_playing());
+        assert!(!mock::is_paused(**generator.handle()));
+        source.pause()?;
+        context.pause()?;
+        assert!(!source.is_playing() && !context.is_playing());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_partial_frames() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let error = context.render(&mut [0.; 3]).unwrap_err();
+        assert!(matches!(error, SynthizerError::InvalidArgument(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn builds_objects_like_any_context() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        context.new_direct_source()?;
+        context.set_gain(0.5)?;
+        assert_eq!(context.get_gain()?, 0.5);
+        Ok(())
+    }
+}
@@ -0,0 +1,379 @@
+//! Doppler shift for moving 3D sources.
+//!
+//! Synthizer has no notion of velocity, so velocities are kept here and
+//! [`Context::update_doppler`] turns them into pitch bends on the buffer
+//! generators of each source, ramped over
+//! [`DopplerConfig::smoothing`] with automation so changes don't click.
+//! Call it after moving things, typically once a frame.
+//!
+//! The shift multiplies whatever pitch bend the application sets, which
+//! reads back unshifted. Only buffer generators are bent, as the only
+//! generators whose pitch bend Synthizer 0.11 reliably honors.
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+use crate::{
+    ffi::*,
+    property,
+    scaled::{self, Layer},
+    BufferGenerator, Context, Handle, Object, Source3D, SynthizerError, WeakHandle,
+};
+
+// Shifts are kept within this factor of no shift, as sources approaching at
+// the speed of sound would otherwise bend infinitely.
+const MAX_SHIFT: f64 = 4.;
+
+type V = [f64; 3];
+
+/// How a context computes Doppler shifts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DopplerConfig {
+    /// In units per second, 343 for meters in air.
+    pub speed_of_sound: f64,
+    /// Scales the effect, with 0 disabling it and values over 1
+    /// exaggerating it.
+    pub doppler_factor: f64,
+    /// Seconds over which each change in pitch is ramped.
+    pub smoothing: f64,
+}
+
+impl Default for DopplerConfig {
+    fn default() -> Self {
+        Self {
+            speed_of_sound: 343.,
+            doppler_factor: 1.,
+            smoothing: 0.05,
+        }
+    }
+}
+
+impl DopplerConfig {
+    fn validate(&self) -> Result<(), SynthizerError> {
+        // Written so NaNs fail too.
+        let valid = self.speed_of_sound > 0.
+            && self.speed_of_sound.is_finite()
+            && self.doppler_factor >= 0.
+            && self.doppler_factor.is_finite()
+            && self.smoothing >= 0.
+            && self.smoothing.is_finite();
+        if valid {
+            Ok(())
+        } else {
+            Err(SynthizerError::InvalidArgument(format!(
+                "Invalid Doppler configuration {:?}",
+                self
+            )))
+        }
+    }
+
+    /// The factor a sound's pitch is multiplied by, from positions and
+    /// velocities in Synthizer's coordinates.
+    fn shift(&self, listener: (V, V), source: (V, V)) -> f64 {
+        let (listener_position, listener_velocity) = listener;
+        let (source_position, source_velocity) = source;
+        let towards_listener = [
+            listener_position[0] - source_position[0],
+            listener_position[1] - source_position[1],
+            listener_position[2] - source_position[2],
+        ];
+        let distance = dot(towards_listener, towards_listener).sqrt();
+        if distance <= f64::EPSILON {
+            return 1.;
+        }
+        let radial = |velocity: V| dot(towards_listener, velocity) / distance;
+        let listener = self.speed_of_sound - self.doppler_factor * radial(listener_velocity);
+        let source = self.speed_of_sound - self.doppler_factor * radial(source_velocity);
+        // Written so NaNs and infinities from sources at the speed of sound
+        // fall back to no shift or the nearest bound.
+        let shift = listener / source;
+        if shift.is_nan() {
+            return 1.;
+        }
+        shift.clamp(1. / MAX_SHIFT, MAX_SHIFT)
+    }
+}
+
+fn dot(a: V, b: V) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// What Doppler needs to know about an object.
+#[derive(Debug, Default)]
+struct DopplerState {
+    // In Synthizer's coordinates.
+    velocity: V,
+    // The rest are only used by contexts or sources, respectively.
+    config: DopplerConfig,
+    sources: Vec<WeakHandle>,
+    generators: Vec<WeakHandle>,
+}
+
+// Keyed by object, along with the object so a reused handle starts afresh.
+// Entries for dropped objects are pruned whenever one is added.
+static STATES: Mutex<BTreeMap<syz_Handle, (WeakHandle, DopplerState)>> =
+    Mutex::new(BTreeMap::new());
+
+fn with_state<T>(handle: &Handle, f: impl FnOnce(&mut DopplerState) -> T) -> T {
+    let mut states = STATES.lock().unwrap();
+    if !states.get(&**handle).is_some_and(|v| v.0.is(handle)) {
+        states.retain(|_, v| v.0.upgrade().is_some());
+        states.insert(**handle, (handle.downgrade(), DopplerState::default()));
+    }
+    f(&mut states.get_mut(&**handle).expect("Just inserted").1)
+}
+
+fn velocity_of(handle: &Handle) -> (f64, f64, f64) {
+    let [x, y, z] = with_state(handle, |v| v.velocity);
+    handle.coordinate_system().from_synthizer((x, y, z))
+}
+
+fn set_velocity_of(handle: &Handle, x: f64, y: f64, z: f64) -> Result<(), SynthizerError> {
+    let valid = x.is_finite() && y.is_finite() && z.is_finite();
+    if !valid {
+        return Err(SynthizerError::InvalidArgument(format!(
+            "Invalid velocity {:?}",
+            (x, y, z)
+        )));
+    }
+    let (x, y, z) = handle.coordinate_system().to_synthizer((x, y, z));
+    with_state(handle, |v| v.velocity = [x, y, z]);
+    Ok(())
+}
+
+fn motion(handle: &Handle) -> Result<(V, V), SynthizerError> {
+    let position = handle.get(property::POSITION)?;
+    let (x, y, z) = handle.coordinate_system().to_synthizer(position);
+    Ok(([x, y, z], with_state(handle, |v| v.velocity)))
+}
+
+fn context_of(source: &Handle) -> Result<Context, SynthizerError> {
+    source
+        .context()
+        .map(Context)
+        .ok_or_else(|| SynthizerError::InvalidArgument("Source has no live context".to_string()))
+}
+
+impl Context {
+    pub fn set_doppler_config(&self, config: DopplerConfig) -> Result<(), SynthizerError> {
+        config.validate()?;
+        with_state(self.handle(), |v| v.config = config);
+        Ok(())
+    }
+
+    pub fn get_doppler_config(&self) -> DopplerConfig {
+        with_state(self.handle(), |v| v.config)
+    }
+
+    /// Sets the listener's velocity, in units per second.
+    pub fn set_velocity(&self, x: f64, y: f64, z: f64) -> Result<(), SynthizerError> {
+        set_velocity_of(self.handle(), x, y, z)
+    }
+
+    pub fn get_velocity(&self) -> (f64, f64, f64) {
+        velocity_of(self.handle())
+    }
+
+    /// Bends the pitch of every generator added with
+    /// [`Source3D::add_doppler_generator`] to match where things are and how
+    /// they're moving now.
+    pub fn update_doppler(&self) -> Result<(), SynthizerError> {
+        let (config, sources) = with_state(self.handle(), |state| {
+            state.sources.retain(|v| v.upgrade().is_some());
+            let sources = state
+                .sources
+                .iter()
+                .filter_map(|v| v.upgrade())
+                .collect::<Vec<_>>();
+            (state.config, sources)
+        });
+        let listener = motion(self.handle())?;
+        for source in sources.iter() {
+            let shift = config.shift(listener, motion(source)?);
+            let generators = with_state(source, |state| {
+                state.generators.retain(|v| v.upgrade().is_some());
+                state
+                    .generators
+                    .iter()
+                    .filter_map(|v| v.upgrade())
+                    .collect::<Vec<_>>()
+            });
+            for generator in generators.iter() {
+                let (pitch_bend, smoothing) = (property::PITCH_BEND, config.smoothing);
+                scaled::set_factor(generator, pitch_bend, Layer::Doppler, shift, smoothing)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Source3D {
+    /// Sets the source's velocity, in units per second.
+    pub fn set_velocity(&self, x: f64, y: f64, z: f64) -> Result<(), SynthizerError> {
+        set_velocity_of(self.handle(), x, y, z)
+    }
+
+    pub fn get_velocity(&self) -> (f64, f64, f64) {
+        velocity_of(self.handle())
+    }
+
+    /// Has [`Context::update_doppler`] bend `generator`'s pitch for as long
+    /// as it's alive. The generator should also be added to this source to be
+    /// heard through it.
+    pub fn add_doppler_generator(&self, generator: &BufferGenerator) -> Result<(), SynthizerError> {
+        let context = context_of(self.handle())?;
+        with_state(self.handle(), |state| {
+            state.generators.retain(|v| !v.is(generator.handle()));
+            state.generators.push(generator.handle().downgrade());
+        });
+        with_state(context.handle(), |state| {
+            if !state.sources.iter().any(|v| v.is(self.handle())) {
+                state.sources.push(self.handle().downgrade());
+            }
+        });
+        Ok(())
+    }
+
+    /// Stops bending `generator`'s pitch, leaving the pitch bend the
+    /// application set.
+    pub fn remove_doppler_generator(
+        &self,
+        generator: &BufferGenerator,
+    ) -> Result<(), SynthizerError> {
+        with_state(self.handle(), |state| {
+            state.generators.retain(|v| !v.is(generator.handle()))
+        });
+        scaled::remove_factor(generator.handle(), property::PITCH_BEND, Layer::Doppler)
+    }
+
+    /// The factor [`Context::update_doppler`] would bend this source's pitch
+    /// by now.
+    pub fn get_doppler_shift(&self) -> Result<f64, SynthizerError> {
+        let context = context_of(self.handle())?;
+        let config = context.get_doppler_config();
+        Ok(config.shift(motion(context.handle())?, motion(self.handle())?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock, CoordinateSystem, Synthizer};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn shifts_by_radial_velocity() {
+        let config = DopplerConfig::default();
+        let still = ([0., 0., 0.], [0., 0., 0.]);
+        let approaching = ([0., 10., 0.], [0., -34.3, 0.]);
+        assert!(close(
+            config.shift(still, approaching),
+            343. / (343. - 34.3)
+        ));
+        let receding = ([0., 10., 0.], [0., 34.3, 0.]);
+        assert!(close(config.shift(still, receding), 343. / (343. + 34.3)));
+        let walking = ([0., 0., 0.], [0., 34.3, 0.]);
+        let source = ([0., 10., 0.], [0., 0., 0.]);
+        assert!(close(config.shift(walking, source), (343. + 34.3) / 343.));
+        let passing = ([0., 10., 0.], [34.3, 0., 0.]);
+        assert!(close(config.shift(still, passing), 1.));
+        let supersonic = ([0., 10., 0.], [0., -343., 0.]);
+        assert_eq!(config.shift(still, supersonic), MAX_SHIFT);
+        let disabled = DopplerConfig {
+            doppler_factor: 0.,
+            ..config
+        };
+        assert!(close(disabled.shift(still, approaching), 1.));
+        assert!(close(config.shift(still, still), 1.));
+    }
+
+    #[test]
+    fn validates_config() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let context = synthizer.new_context()?;
+        let config = DopplerConfig {
+            speed_of_sound: 1500.,
+            ..Default::default()
+        };
+        context.set_doppler_config(config)?;
+        assert_eq!(context.get_doppler_config(), config);
+        for bad in [0., -1., f64::NAN].iter() {
+            let error = context
+                .set_doppler_config(DopplerConfig {
+                    speed_of_sound: *bad,
+                    ..config
+                })
+                .unwrap_err();
+            assert!(matches!(error, SynthizerError::InvalidArgument(_)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn ramps_pitch_bend() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        let generator = context.new_buffer_generator()?;
+        generator.set_pitch_bend(2.)?;
+        source.add_doppler_generator(&generator)?;
+        source.set_position(0., 10., 0.)?;
+        source.set_velocity(0., -34.3, 0.)?;
+        let shift = source.get_doppler_shift()?;
+        assert!(close(shift, 343. / (343. - 34.3)));
+        context.update_doppler()?;
+        let none = SYZ_INTERPOLATION_TYPES_SYZ_INTERPOLATION_TYPE_NONE as i32;
+        let linear = SYZ_INTERPOLATION_TYPES_SYZ_INTERPOLATION_TYPE_LINEAR as i32;
+        let points = mock::automation(**generator.handle(), property::PITCH_BEND.id());
+        let start = points[0].0;
+        assert_eq!((points[0].1, points[0].2[0]), (none, 2.));
+        assert_eq!(points[1].1, linear);
+        assert!(close(points[1].0, start + 0.05) && close(points[1].2[0], 2. * shift));
+        source.set_velocity(0., 0., 0.)?;
+        context.update_doppler()?;
+        let points = mock::automation(**generator.handle(), property::PITCH_BEND.id());
+        assert_eq!(points.len(), 2);
+        assert!(close(points[1].2[0], 2.));
+        source.remove_doppler_generator(&generator)?;
+        assert!(mock::automation(**generator.handle(), property::PITCH_BEND.id()).is_empty());
+        assert_eq!(generator.get_pitch_bend()?, 2.);
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_pitch_bend_set_while_bending() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        let generator = context.new_buffer_generator()?;
+        source.add_doppler_generator(&generator)?;
+        source.set_position(0., 10., 0.)?;
+        source.set_velocity(0., -34.3, 0.)?;
+        context.update_doppler()?;
+        let shift = source.get_doppler_shift()?;
+        generator.set_pitch_bend(1.5)?;
+        assert_eq!(generator.get_pitch_bend()?, 1.5);
+        let points = mock::automation(**generator.handle(), property::PITCH_BEND.id());
+        let last = points.last().unwrap();
+        assert!(close(last.2[0], 1.5 * shift));
+        source.remove_doppler_generator(&generator)?;
+        assert_eq!(generator.get_pitch_bend()?, 1.5);
+        Ok(())
+    }
+
+    #[test]
+    fn moving_listener_shifts_pitch() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        context.set_coordinate_system(CoordinateSystem::Y_UP_RIGHT_HANDED);
+        let source = context.new_source3d()?;
+        source.set_position(0., 0., -10.)?;
+        context.set_velocity(0., 0., -34.3)?;
+        assert_eq!(context.get_velocity(), (0., 0., -34.3));
+        assert!(close(source.get_doppler_shift()?, (343. + 34.3) / 343.));
+        assert!(context.set_velocity(f64::NAN, 0., 0.).is_err());
+        Ok(())
+    }
+}
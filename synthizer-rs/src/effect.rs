@@ -0,0 +1,359 @@
+use std::ptr::null_mut;
+
+use crate::{ffi::*, property, BiquadConfig, Context, Handle, Object, Source, SynthizerError};
+
+/// Effects which sources can be routed to.
+pub trait Effect: Object {
+    /// Clears the effect's internal state, such as a reverb's tail.
+    fn reset(&self) -> Result<(), SynthizerError> {
+        wrap!(unsafe { syz_effectReset(**self.handle()) })
+    }
+
+    property!(gain: f64 = property::GAIN);
+    property!(
+        /// Filters everything routed into the effect.
+        filter_input: BiquadConfig = property::FILTER_INPUT
+    );
+}
+
+/// How a source is routed to an effect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RouteConfig {
+    /// How much of the source is sent to the effect.
+    pub gain: f64,
+    /// Seconds over which changes to the route fade in.
+    pub fade_time: f64,
+}
+
+impl Default for RouteConfig {
+    fn default() -> Self {
+        Self {
+            gain: 1.,
+            fade_time: 0.03,
+        }
+    }
+}
+
+impl RouteConfig {
+    pub(crate) fn to_syz(self) -> Result<syz_RouteConfig, SynthizerError> {
+        let mut config = unsafe { std::mem::zeroed::<syz_RouteConfig>() };
+        wrap!(unsafe { syz_initRouteConfig(&mut config) })?;
+        config.gain = self.gain;
+        config.fade_time = self.fade_time;
+        Ok(config)
+    }
+}
+
+/// The longest delay, in seconds, Synthizer allows on an echo tap.
+pub const MAX_ECHO_DELAY: f64 = 5.;
+
+/// One tap of a [`GlobalEcho`]: a copy of the input played back after
+/// `delay` seconds, with a gain for each channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EchoTap {
+    pub delay: f64,
+    pub gain_l: f64,
+    pub gain_r: f64,
+}
+
+impl EchoTap {
+    pub fn new(delay: f64, gain_l: f64, gain_r: f64) -> Self {
+        Self {
+            delay,
+            gain_l,
+            gain_r,
+        }
+    }
+
+    /// `count` taps `delay` seconds apart, alternating between the left and
+    /// right channels, with each tap `feedback` times as loud as the last.
+    pub fn ping_pong(delay: f64, feedback: f64, count: usize) -> Vec<Self> {
+        let mut gain = 1.;
+        (1..=count)
+            .map(|i| {
+                let tap = if i % 2 == 1 {
+                    Self::new(delay * i as f64, gain, 0.)
+                } else {
+                    Self::new(delay * i as f64, 0., gain)
+                };
+                gain *= feedback;
+                tap
+            })
+            .collect()
+    }
+
+    /// `count` centered taps starting at `delay` and `spacing` seconds
+    /// apart, the first at `gain` and each later one quieter.
+    pub fn slapback(delay: f64, spacing: f64, count: usize, gain: f64) -> Vec<Self> {
+        (0..count)
+            .map(|i| {
+                let gain = gain / (i + 1) as f64;
+                Self::new(delay + spacing * i as f64, gain, gain)
+            })
+            .collect()
+    }
+
+    /// `count` taps at random delays up to `max_delay`, randomly panned and
+    /// falling off linearly from `gain`, approximating a room's early
+    /// reflections. The same `seed` always gives the same pattern.
+    pub fn early_reflections(count: usize, max_delay: f64, gain: f64, seed: u64) -> Vec<Self> {
+        // xorshift64*, which is plenty random for this and needs no
+        // dependencies. A zero state would only ever produce zeros.
+        let mut state = (seed ^ 0x9e37_79b9_7f4a_7c15).max(1);
+        let mut random = move || {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            (state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let mut taps = (0..count)
+            .map(|_| {
+                let delay = max_delay * (1. - random());
+                let pan = random();
+                let gain = gain * (1. - delay / max_delay).max(0.1);
+                Self::new(delay, gain * (1. - pan).sqrt(), gain * pan.sqrt())
+            })
+            .collect::<Vec<_>>();
+        taps.sort_by(|a, b| a.delay.total_cmp(&b.delay));
+        taps
+    }
+
+    fn validate(&self) -> Result<(), SynthizerError> {
+        if !(0. ..=MAX_ECHO_DELAY).contains(&self.delay) {
+            return Err(SynthizerError::InvalidArgument(format!(
+                "Echo tap delay of {} seconds isn't between 0 and {}",
+                self.delay, MAX_ECHO_DELAY
+            )));
+        }
+        if !self.gain_l.is_finite() || !self.gain_r.is_finite() {
+            return Err(SynthizerError::InvalidArgument(format!(
+                "Echo tap gains must be finite, not {} and {}",
+                self.gain_l, self.gain_r
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A multi-tap echo shared by every source routed to it.
+#[derive(Clone, Debug)]
+pub struct GlobalEcho(pub(crate) Handle);
+
+impl GlobalEcho {
+    pub(crate) fn new(context: &Context) -> Result<Self, SynthizerError> {
+        let mut handle = 0;
+        wrap!(
+            unsafe { syz_createGlobalEcho(&mut handle, **context, null_mut(), null_mut(), None) },
+            Self(Handle::new_in(handle, context.handle()))
+        )
+    }
+
+    /// Replaces every tap of the echo. The taps are copied, and can be in
+    /// any order.
+    pub fn set_taps(&self, taps: &[EchoTap]) -> Result<(), SynthizerError> {
+        let taps = taps
+            .iter()
+            .map(|v| {
+                v.validate()?;
+                Ok(syz_EchoTapConfig {
+                    delay: v.delay,
+                    gain_l: v.gain_l,
+                    gain_r: v.gain_r,
+                })
+            })
+            .collect::<Result<Vec<_>, SynthizerError>>()?;
+        wrap!(unsafe { syz_globalEchoSetTaps(**self.handle(), taps.len() as u32, taps.as_ptr()) })
+    }
+}
+
+make_object!(GlobalEcho);
+
+impl Effect for GlobalEcho {}
+
+/// A reverb based on a feedback delay network.
+#[derive(Clone, Debug)]
+pub struct GlobalFdnReverb(pub(crate) Handle);
+
+impl GlobalFdnReverb {
+    pub(crate) fn new(context: &Context) -> Result<Self, SynthizerError> {
+        let mut handle = 0;
+        wrap!(
+            unsafe {
+                syz_createGlobalFdnReverb(&mut handle, **context, null_mut(), null_mut(), None)
+            },
+            Self(Handle::new_in(handle, context.handle()))
+        )
+    }
+
+    property!(pub mean_free_path: f64 = property::MEAN_FREE_PATH);
+    property!(pub t60: f64 = property::T60);
+    property!(pub late_reflections_lf_rolloff: f64 = property::LATE_REFLECTIONS_LF_ROLLOFF);
+    property!(pub late_reflections_lf_reference: f64 = property::LATE_REFLECTIONS_LF_REFERENCE);
+    property!(pub late_reflections_hf_rolloff: f64 = property::LATE_REFLECTIONS_HF_ROLLOFF);
+    property!(pub late_reflections_hf_reference: f64 = property::LATE_REFLECTIONS_HF_REFERENCE);
+    property!(pub late_reflections_diffusion: f64 = property::LATE_REFLECTIONS_DIFFUSION);
+    property!(
+        pub late_reflections_modulation_depth: f64 = property::LATE_REFLECTIONS_MODULATION_DEPTH
+    );
+    property!(
+        pub late_reflections_modulation_frequency: f64 =
+            property::LATE_REFLECTIONS_MODULATION_FREQUENCY
+    );
+    property!(pub late_reflections_delay: f64 = property::LATE_REFLECTIONS_DELAY);
+}
+
+make_object!(GlobalFdnReverb);
+
+impl Effect for GlobalFdnReverb {}
+
+impl Context {
+    pub fn new_global_echo(&mut self) -> Result<GlobalEcho, SynthizerError> {
+        GlobalEcho::new(self)
+    }
+
+    pub fn new_global_fdn_reverb(&mut self) -> Result<GlobalFdnReverb, SynthizerError> {
+        GlobalFdnReverb::new(self)
+    }
+
+    /// Sends `output` to `input`, or updates the existing route between them.
+    pub fn config_route(
+        &self,
+        output: &impl Source,
+        input: &impl Effect,
+        config: RouteConfig,
+    ) -> Result<(), SynthizerError> {
+        let config = config.to_syz()?;
+        wrap!(unsafe {
+            syz_routingConfigRoute(
+                **self.handle(),
+                **output.handle(),
+                **input.handle(),
+                &config,
+            )
+        })
+    }
+
+    /// Removes the route from `output` to `input`, fading it out over
+    /// `fade_out` seconds.
+    pub fn remove_route(
+        &self,
+        output: &impl Source,
+        input: &impl Effect,
+        fade_out: f64,
+    ) -> Result<(), SynthizerError> {
+        wrap!(unsafe {
+            syz_routingRemoveRoute(
+                **self.handle(),
+                **output.handle(),
+                **input.handle(),
+                fade_out,
+            )
+        })
+    }
+
+    /// Removes every route from `output`, fading them out over `fade_out`
+    /// seconds.
+    pub fn remove_all_routes(
+        &self,
+        output: &impl Source,
+        fade_out: f64,
+    ) -> Result<(), SynthizerError> {
+        wrap!(unsafe { syz_routingRemoveAllRoutes(**self.handle(), **output.handle(), fade_out) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock, Synthizer};
+
+    #[test]
+    fn routes_sources_to_effects() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        let echo = context.new_global_echo()?;
+        let reverb = context.new_global_fdn_reverb()?;
+        let config = RouteConfig {
+            gain: 0.5,
+            fade_time: 0.1,
+        };
+        context.config_route(&source, &echo, config)?;
+        context.config_route(&source, &reverb, RouteConfig::default())?;
+        let (output, input) = (**source.handle(), **reverb.handle());
+        assert_eq!(mock::route(output, **echo.handle()), Some((0.5, 0.1)));
+        assert_eq!(mock::route(output, input), Some((1., 0.03)));
+        context.remove_route(&source, &reverb, 0.)?;
+        assert_eq!(mock::route(output, input), None);
+        context.remove_all_routes(&source, 0.)?;
+        assert_eq!(mock::route(output, **echo.handle()), None);
+        Ok(())
+    }
+
+    #[test]
+    fn sets_echo_taps() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let echo = context.new_global_echo()?;
+        let taps = [EchoTap::new(0.1, 1., 0.5), EchoTap::new(0.3, 0., 1.)];
+        echo.set_taps(&taps)?;
+        assert_eq!(mock::echo_taps(**echo.handle()), taps);
+        echo.set_taps(&[])?;
+        assert!(mock::echo_taps(**echo.handle()).is_empty());
+        for tap in [
+            EchoTap::new(MAX_ECHO_DELAY + 0.1, 1., 1.),
+            EchoTap::new(-1., 1., 1.),
+            EchoTap::new(f64::NAN, 1., 1.),
+            EchoTap::new(1., f64::INFINITY, 1.),
+        ]
+        .iter()
+        {
+            let error = echo.set_taps(&[*tap]).unwrap_err();
+            assert!(matches!(error, SynthizerError::InvalidArgument(_)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn tap_patterns() {
+        let taps = EchoTap::ping_pong(0.25, 0.5, 3);
+        assert_eq!(
+            taps,
+            [
+                EchoTap::new(0.25, 1., 0.),
+                EchoTap::new(0.5, 0., 0.5),
+                EchoTap::new(0.75, 0.25, 0.),
+            ]
+        );
+        let taps = EchoTap::slapback(0.125, 0.125, 2, 0.6);
+        assert_eq!(
+            taps,
+            [EchoTap::new(0.125, 0.6, 0.6), EchoTap::new(0.25, 0.3, 0.3)]
+        );
+        let taps = EchoTap::early_reflections(16, 0.1, 0.8, 42);
+        assert_eq!(taps.len(), 16);
+        assert!(taps.windows(2).all(|v| v[0].delay <= v[1].delay));
+        assert!(taps.iter().all(|v| v.delay > 0. && v.delay <= 0.1));
+        assert!(taps.iter().all(|v| v.validate().is_ok()));
+        assert_eq!(taps, EchoTap::early_reflections(16, 0.1, 0.8, 42));
+        assert_ne!(taps, EchoTap::early_reflections(16, 0.1, 0.8, 43));
+    }
+
+    #[test]
+    fn reverb_properties() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let reverb = context.new_global_fdn_reverb()?;
+        reverb.set_t60(2.5)?;
+        reverb.set_mean_free_path(0.05)?;
+        reverb.set_gain(0.8)?;
+        assert_eq!(reverb.get_t60()?, 2.5);
+        assert_eq!(reverb.get_mean_free_path()?, 0.05);
+        assert_eq!(reverb.get_gain()?, 0.8);
+        let filter = BiquadConfig::lowpass(500., BiquadConfig::DEFAULT_Q)?;
+        reverb.set_filter_input(filter)?;
+        assert_eq!(reverb.get_filter_input()?, filter);
+        reverb.reset()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    ffi::{CStr, NulError},
+    io,
+    sync::Arc,
+};
+
+use thiserror::Error;
+
+use crate::ffi::*;
+
+/// An error reported by Synthizer itself.
+///
+/// Synthizer currently uses a single error code for everything, so the
+/// message is usually the only thing telling failures apart.
+#[derive(Clone, Debug, Error)]
+#[error("{message} (code {code})")]
+pub struct NativeError {
+    code: syz_ErrorCode,
+    message: String,
+}
+
+impl NativeError {
+    pub fn code(&self) -> syz_ErrorCode {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Everything that can go wrong when calling into Synthizer.
+///
+/// Synthizer doesn't report what kind of error occurred, so native errors are
+/// categorized from their messages. Anything we don't recognize ends up in
+/// `Other`, so match on the categories you care about and fall through for the
+/// rest.
+#[derive(Clone, Debug, Error)]
+#[non_exhaustive]
+pub enum SynthizerError {
+    #[error("Invalid handle")]
+    InvalidHandle(#[source] NativeError),
+    #[error("Handle of the wrong object type")]
+    WrongObjectType(#[source] NativeError),
+    #[error("Property not supported by this object")]
+    UnsupportedProperty(#[source] NativeError),
+    #[error("Unable to decode audio")]
+    Decoding(#[source] NativeError),
+    #[error("Resource not found")]
+    NotFound(#[source] NativeError),
+    #[error("I/O error")]
+    Io(#[source] NativeError),
+    #[error("Value out of range")]
+    OutOfRange(#[source] NativeError),
+    #[error("Synthizer is not initialized")]
+    Uninitialized(#[source] NativeError),
+    #[error("Synthizer error")]
+    Other(#[source] NativeError),
+    #[error("String contains a nul byte")]
+    InvalidString(#[from] NulError),
+    /// An argument was rejected before it reached Synthizer.
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    /// Writing rendered audio failed.
+    #[error("Unable to write output")]
+    Output(#[source] Arc<io::Error>),
+}
+
+impl SynthizerError {
+    /// Builds an error from Synthizer's last error on this thread, which
+    /// must be read before anything else calls into Synthizer.
+    pub(crate) fn last(code: syz_ErrorCode) -> Self {
+        let message = unsafe { syz_getLastErrorMessage() };
+        let message = if message.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(message) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        Self::native(code, message)
+    }
+
+    fn native(code: syz_ErrorCode, message: String) -> Self {
+        let categorize: fn(NativeError) -> Self = match message.as_str() {
+            "C handle is invalid" => Self::InvalidHandle,
+            m if m.starts_with("This handle is already dead") => Self::InvalidHandle,
+            "Handle of the wrong type provided" => Self::WrongObjectType,
+            "Not a valid property for this object type" | "Property type mismatch" => {
+                Self::UnsupportedProperty
+            }
+            "Unsupported audio format." => Self::Decoding,
+            "Resource not found" => Self::NotFound,
+            "Unable to open file"
+            | "Unable to seek."
+            | "Out of range seek"
+            | "Attempt to seek past end of stream"
+            | "Unsupported byte stream operation"
+            | "custom byte stream callback error" => Self::Io,
+            m if m.starts_with("Unable to get file position")
+                || m.starts_with("Custom byte stream error")
+                || m.starts_with("Unregistered protocol")
+                || m.starts_with("Protocol ") =>
+            {
+                Self::Io
+            }
+            "Value out of range" | "Delay is too long" | "Too many channels" => Self::OutOfRange,
+            m if m.starts_with("Invalid ") || m.ends_with("must have at least 1 channel") => {
+                Self::OutOfRange
+            }
+            "The library is not initialized." => Self::Uninitialized,
+            _ => Self::Other,
+        };
+        categorize(NativeError { code, message })
+    }
+
+    /// The underlying Synthizer error, if this came from Synthizer.
+    pub fn native_error(&self) -> Option<&NativeError> {
+        match self {
+            Self::InvalidHandle(e)
+            | Self::WrongObjectType(e)
+            | Self::UnsupportedProperty(e)
+            | Self::Decoding(e)
+            | Self::NotFound(e)
+            | Self::Io(e)
+            | Self::OutOfRange(e)
+            | Self::Uninitialized(e)
+            | Self::Other(e) => Some(e),
+            Self::InvalidString(_) | Self::InvalidArgument(_) | Self::Output(_) => None,
+        }
+    }
+
+    /// Synthizer's message for this error, if this came from Synthizer.
+    pub fn message(&self) -> Option<&str> {
+        self.native_error().map(|v| v.message())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+    use crate::{Buffer, Handle, Object, Protocol, Synthizer};
+
+    #[test]
+    fn categorizes_default_messages() {
+        let category = |message: &str| SynthizerError::native(1, message.into());
+        assert!(matches!(
+            category("C handle is invalid"),
+            SynthizerError::InvalidHandle(_)
+        ));
+        assert!(matches!(
+            category("Handle of the wrong type provided"),
+            SynthizerError::WrongObjectType(_)
+        ));
+        assert!(matches!(
+            category("Property type mismatch"),
+            SynthizerError::UnsupportedProperty(_)
+        ));
+        assert!(matches!(
+            category("Unsupported audio format."),
+            SynthizerError::Decoding(_)
+        ));
+        assert!(matches!(
+            category("Resource not found"),
+            SynthizerError::NotFound(_)
+        ));
+        assert!(matches!(
+            category("Unregistered protocol foo"),
+            SynthizerError::Io(_)
+        ));
+        assert!(matches!(
+            category("Invalid azimuth"),
+            SynthizerError::OutOfRange(_)
+        ));
+        assert!(matches!(
+            category("The library is not initialized."),
+            SynthizerError::Uninitialized(_)
+        ));
+        let other = SynthizerError::native(7, "Something new".into());
+        assert!(matches!(other, SynthizerError::Other(_)));
+        let native = other.native_error().unwrap();
+        assert_eq!(native.code(), 7);
+        assert_eq!(native.message(), "Something new");
+    }
+
+    #[test]
+    fn captures_last_error_message() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let context = synthizer.new_context()?;
+        let error = Handle::from_borrowed(u64::MAX, context.handle()).unwrap_err();
+        assert!(matches!(error, SynthizerError::InvalidHandle(_)));
+        assert_eq!(error.message(), Some("C handle is invalid"));
+        let source = error.source().unwrap();
+        assert!(source.to_string().contains("C handle is invalid"));
+        Ok(())
+    }
+
+    #[test]
+    fn rust_errors_chain_their_source() -> Result<(), SynthizerError> {
+        let _synthizer = Synthizer::new()?;
+        let error = Buffer::new(Protocol::File, "bad\0.wav".as_ref(), "").unwrap_err();
+        assert!(matches!(error, SynthizerError::InvalidString(_)));
+        assert!(error.source().unwrap().is::<NulError>());
+        assert_eq!(error.message(), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+//! Async delivery of context events, behind the `async` feature.
+//!
+//! Each context with async consumers gets one background thread which drains
+//! its native event queue and hands events to every stream and future
+//! interested in them. The thread stops once the last of them is dropped.
+//!
+//! Synthizer has one event queue per context, and events taken by this thread
+//! never reach `Context::events`, `Context::event_receiver` or
+//! `Context::on_event`. Consume each context's events either asynchronously
+//! or synchronously, never both.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context as TaskContext, Poll},
+    time::Duration,
+};
+
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    Stream, StreamExt,
+};
+
+use crate::{events, ffi::*, Context, Event, EventKind, Handle, Object, SynthizerError};
+
+// How often the background thread checks for events.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+struct Subscriber {
+    filter: Box<dyn Fn(&Event) -> bool + Send>,
+    sender: UnboundedSender<Event>,
+}
+
+#[derive(Default)]
+struct Hub {
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+impl Hub {
+    fn broadcast(&self, event: Event) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|v| !v.sender.is_closed());
+        for subscriber in subscribers.iter() {
+            if (subscriber.filter)(&event) {
+                subscriber.sender.unbounded_send(event.clone()).ok();
+            }
+        }
+    }
+}
+
+static HUBS: Mutex<BTreeMap<syz_Handle, Arc<Hub>>> = Mutex::new(BTreeMap::new());
+
+/// Removes a context's hub once its background thread stops, which closes
+/// every subscriber. A newer hub for the same context is left alone.
+struct Unregister(syz_Handle, Arc<Hub>);
+
+impl Drop for Unregister {
+    fn drop(&mut self) {
+        let mut hubs = HUBS.lock().unwrap();
+        if hubs.get(&self.0).is_some_and(|v| Arc::ptr_eq(v, &self.1)) {
+            hubs.remove(&self.0);
+        }
+    }
+}
+
+/// Whether `hub` has nobody left to deliver to, in which case it's removed so
+/// the next subscriber starts a new one.
+fn abandoned(raw: syz_Handle, hub: &Arc<Hub>) -> bool {
+    // Subscribing holds this lock throughout, so nobody can join the hub
+    // between checking it and removing it.
+    let mut hubs = HUBS.lock().unwrap();
+    let mut subscribers = hub.subscribers.lock().unwrap();
+    subscribers.retain(|v| !v.sender.is_closed());
+    if !subscribers.is_empty() {
+        return false;
+    }
+    if hubs.get(&raw).is_some_and(|v| Arc::ptr_eq(v, hub)) {
+        hubs.remove(&raw);
+    }
+    true
+}
+
+/// Subscribes to the events of `context` which pass `filter`, starting its
+/// background thread if needed. The receiver ends when the context is
+/// dropped.
+fn subscribe(
+    context: &Handle,
+    filter: impl Fn(&Event) -> bool + Send + 'static,
+) -> Result<UnboundedReceiver<Event>, SynthizerError> {
+    let mut hubs = HUBS.lock().unwrap();
+    let raw = **context;
+    let hub = match hubs.get(&raw) {
+        Some(hub) => hub.clone(),
+        None => {
+            wrap!(unsafe { syz_contextEnableEvents(raw) })?;
+            let hub = Arc::new(Hub::default());
+            hubs.insert(raw, hub.clone());
+            let (done_hub, thread_hub) = (hub.clone(), hub.clone());
+            let unregister = Unregister(raw, hub.clone());
+            events::spawn_poller(
+                &Context(context.clone()),
+                POLL_INTERVAL,
+                move || abandoned(raw, &done_hub),
+                move |event| {
+                    let _ = &unregister;
+                    thread_hub.broadcast(event);
+                    true
+                },
+            );
+            hub
+        }
+    };
+    let (sender, receiver) = unbounded();
+    hub.subscribers.lock().unwrap().push(Subscriber {
+        filter: Box::new(filter),
+        sender,
+    });
+    Ok(receiver)
+}
+
+/// A stream of a context's events.
+///
+/// Created by [`Context::event_stream`](crate::Context::event_stream). Ends
+/// once every clone of the context is dropped.
+#[derive(Debug)]
+pub struct EventStream(UnboundedReceiver<Event>);
+
+impl Stream for EventStream {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Event>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+impl Context {
+    /// Streams this context's events, enabling them if needed.
+    ///
+    /// Any number of streams can be open at once, and each sees every event
+    /// from the time it was created. While any stream or `finished` future is
+    /// alive, events are taken from the context's queue for them, so they
+    /// can't also be drained with `events`, `event_receiver` or `on_event`.
+    pub fn event_stream(&self) -> Result<EventStream, SynthizerError> {
+        subscribe(self.handle(), |_| true).map(EventStream)
+    }
+}
+
+/// Resolves once an object reports that it finished.
+///
+/// Created by `finished` on generators and fades. Resolves with an error if
+/// the object's context goes away first, since the event can then never
+/// arrive.
+#[derive(Debug)]
+pub struct Finished(Result<UnboundedReceiver<Event>, Option<SynthizerError>>);
+
+impl Finished {
+    pub(crate) fn new(handle: &Handle) -> Self {
+        Self::when(handle, EventKind::Finished)
+    }
+
+    /// Resolves on the first event of `kind` from `handle`.
+    pub(crate) fn when(handle: &Handle, kind: EventKind) -> Self {
+        let context = match handle.context() {
+            Some(context) => context,
+            None => {
+                return Self(Err(Some(SynthizerError::InvalidArgument(
+                    "Object has no live context".to_string(),
+                ))))
+            }
+        };
+        let raw = **handle;
+        let receiver = subscribe(&context, move |event| {
+            event.kind == kind && **event.source.handle() == raw
+        });
+        Self(receiver.map_err(Some))
+    }
+}
+
+impl Future for Finished {
+    type Output = Result<(), SynthizerError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        match &mut self.0 {
+            Ok(receiver) => receiver.poll_next_unpin(cx).map(|event| match event {
+                Some(_) => Ok(()),
+                None => Err(SynthizerError::InvalidArgument(
+                    "Context dropped before the object finished".to_string(),
+                )),
+            }),
+            Err(e) => Poll::Ready(Err(e.take().expect("Polled after completion"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{executor::block_on, FutureExt, StreamExt};
+
+    use super::*;
+    use crate::{mock, Synthizer};
+
+    #[test]
+    fn streams_see_every_event() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let generator = context.new_buffer_generator()?;
+        let mut first = context.event_stream()?;
+        let mut second = context.event_stream()?;
+        mock::push_event(
+            **context.handle(),
+            SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_LOOPED,
+            **generator.handle(),
+            0,
+        );
+        let event = block_on(first.next()).unwrap();
+        assert_eq!(event.kind, EventKind::Looped);
+        let event = block_on(second.next()).unwrap();
+        assert_eq!(**event.source.handle(), **generator.handle());
+        Ok(())
+    }
+
+    #[test]
+    fn generators_report_finishing() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let generator = context.new_buffer_generator()?;
+        let other = context.new_buffer_generator()?;
+        let mut finished = generator.finished();
+        // Subscribed after `finished`, so it sees each event after it has.
+        let mut stream = context.event_stream()?;
+        let events = [
+            (SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_FINISHED, **other.handle()),
+            (SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_LOOPED, **generator.handle()),
+        ];
+        for (kind, source) in events.iter() {
+            mock::push_event(**context.handle(), *kind, *source, 0);
+        }
+        for _ in 0..events.len() {
+            block_on(stream.next()).unwrap();
+        }
+        assert!((&mut finished).now_or_never().is_none());
+        mock::push_event(
+            **context.handle(),
+            SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_FINISHED,
+            **generator.handle(),
+            0,
+        );
+        block_on(finished)
+    }
+
+    #[test]
+    fn hubs_stop_without_subscribers() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let generator = context.new_buffer_generator()?;
+        let raw = **context.handle();
+        let stream = context.event_stream()?;
+        assert!(HUBS.lock().unwrap().contains_key(&raw));
+        drop(stream);
+        for _ in 0..200 {
+            if !HUBS.lock().unwrap().contains_key(&raw) {
+                // A new subscriber gets a working hub of its own.
+                let mut stream = context.event_stream()?;
+                let looped = SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_LOOPED;
+                mock::push_event(raw, looped, **generator.handle(), 0);
+                assert!(block_on(stream.next()).is_some());
+                return Ok(());
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        panic!("Hub outlived its subscribers");
+    }
+
+    #[test]
+    fn finishing_fails_when_the_context_is_dropped() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let generator = context.new_buffer_generator()?;
+        let finished = generator.finished();
+        drop(context);
+        assert!(matches!(
+            block_on(finished),
+            Err(SynthizerError::InvalidArgument(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+//! Events Synthizer sends when things happen to objects in a context.
+//!
+//! Events must first be enabled with
+//! [`Context::enable_events`](crate::Context::enable_events), after which they
+//! queue up until drained by polling
+//! [`Context::events`](crate::Context::events), or delivered from a
+//! background thread with [`Context::event_receiver`](crate::Context::event_receiver)
+//! or [`Context::on_event`](crate::Context::on_event).
+//!
+//! Synthizer 0.11 only reports generators finishing and looping, and user
+//! automation events. It has no events for decoding errors or freed objects,
+//! so those can't be delivered. Decoding errors are returned by whatever
+//! created the buffer or generator instead.
+
+use std::{
+    ops::Deref,
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    ffi::*, AngularPannedSource, Buffer, BufferGenerator, Context, DirectSource,
+    FastSineBankGenerator, GlobalEcho, GlobalFdnReverb, Handle, NoiseGenerator, Object,
+    ScalarPannedSource, Source3D, StreamingGenerator, SynthizerError,
+};
+
+/// What an event is about.
+///
+/// These are all the kinds Synthizer 0.11 reports.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum EventKind {
+    /// A generator reached its end and stopped.
+    Finished,
+    /// A looping generator reached its end and started over.
+    Looped,
+    /// A user event scheduled with automation, with its parameter.
+    UserAutomation(u64),
+}
+
+/// The object an event came from.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum EventSource {
+    Context(Context),
+    Buffer(Buffer),
+    BufferGenerator(BufferGenerator),
+    StreamingGenerator(StreamingGenerator),
+    NoiseGenerator(NoiseGenerator),
+    FastSineBankGenerator(FastSineBankGenerator),
+    DirectSource(DirectSource),
+    AngularPannedSource(AngularPannedSource),
+    ScalarPannedSource(ScalarPannedSource),
+    Source3D(Source3D),
+    GlobalEcho(GlobalEcho),
+    GlobalFdnReverb(GlobalFdnReverb),
+    /// An object this crate doesn't have a type for.
+    Other(Handle),
+}
+
+impl EventSource {
+    fn new(handle: Handle) -> Result<Self, SynthizerError> {
+        let source = match handle.object_type()? as u32 {
+            v if v == SYZ_OBJECT_TYPE_SYZ_OTYPE_CONTEXT as u32 => Self::Context(Context(handle)),
+            v if v == SYZ_OBJECT_TYPE_SYZ_OTYPE_BUFFER as u32 => Self::Buffer(Buffer(handle)),
+            v if v == SYZ_OBJECT_TYPE_SYZ_OTYPE_BUFFER_GENERATOR as u32 => {
+                Self::BufferGenerator(BufferGenerator(handle))
+            }
+            v if v == SYZ_OBJECT_TYPE_SYZ_OTYPE_STREAMING_GENERATOR as u32 => {
+                Self::StreamingGenerator(StreamingGenerator(handle))
+            }
+            v if v == SYZ_OBJECT_TYPE_SYZ_OTYPE_NOISE_GENERATOR as u32 => {
+                Self::NoiseGenerator(NoiseGenerator(handle))
+            }
+            v if v == SYZ_OBJECT_TYPE_SYZ_OTYPE_FAST_SINE_BANK_GENERATOR as u32 => {
+                Self::FastSineBankGenerator(FastSineBankGenerator(handle))
+            }
+            v if v == SYZ_OBJECT_TYPE_SYZ_OTYPE_DIRECT_SOURCE as u32 => {
+                Self::DirectSource(DirectSource(handle))
+            }
+            v if v == SYZ_OBJECT_TYPE_SYZ_OTYPE_ANGULAR_PANNED_SOURCE as u32 => {
+                Self::AngularPannedSource(AngularPannedSource(handle))
+            }
+            v if v == SYZ_OBJECT_TYPE_SYZ_OTYPE_SCALAR_PANNED_SOURCE as u32 => {
+                Self::ScalarPannedSource(ScalarPannedSource(handle))
+            }
+            v if v == SYZ_OBJECT_TYPE_SYZ_OTYPE_SOURCE_3D as u32 => {
+                Self::Source3D(Source3D(handle))
+            }
+            v if v == SYZ_OBJECT_TYPE_SYZ_OTYPE_GLOBAL_ECHO as u32 => {
+                Self::GlobalEcho(GlobalEcho(handle))
+            }
+            v if v == SYZ_OBJECT_TYPE_SYZ_OTYPE_GLOBAL_FDN_REVERB as u32 => {
+                Self::GlobalFdnReverb(GlobalFdnReverb(handle))
+            }
+            _ => Self::Other(handle),
+        };
+        Ok(source)
+    }
+
+    /// The handle of the object, whatever its type.
+    pub fn handle(&self) -> &Handle {
+        match self {
+            Self::Context(v) => &v.0,
+            Self::Buffer(v) => &v.0,
+            Self::BufferGenerator(v) => &v.0,
+            Self::StreamingGenerator(v) => &v.0,
+            Self::NoiseGenerator(v) => &v.0,
+            Self::FastSineBankGenerator(v) => &v.0,
+            Self::DirectSource(v) => &v.0,
+            Self::AngularPannedSource(v) => &v.0,
+            Self::ScalarPannedSource(v) => &v.0,
+            Self::Source3D(v) => &v.0,
+            Self::GlobalEcho(v) => &v.0,
+            Self::GlobalFdnReverb(v) => &v.0,
+            Self::Other(v) => v,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub kind: EventKind,
+    pub source: EventSource,
+}
+
+impl Event {
+    /// Converts a raw event, releasing the references it holds.
+    fn new(mut event: syz_Event, context: &Context) -> Result<Option<Self>, SynthizerError> {
+        let kind = match event.type_ as u32 {
+            v if v == SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_INVALID as u32 => return Ok(None),
+            v if v == SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_FINISHED as u32 => Some(EventKind::Finished),
+            v if v == SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_LOOPED as u32 => Some(EventKind::Looped),
+            v if v == SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_USER_AUTOMATION as u32 => {
+                Some(EventKind::UserAutomation(unsafe {
+                    event.payload.user_automation.param
+                }))
+            }
+            _ => None,
+        };
+        let source = Handle::from_borrowed(event.source, context.handle());
+        unsafe { syz_eventDeinit(&mut event) };
+        // Skip over kinds of event newer than this crate.
+        match kind {
+            Some(kind) => Ok(Some(Event {
+                kind,
+                source: EventSource::new(source?)?,
+            })),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Iterates over a context's pending events.
+///
+/// Created by [`Context::events`](crate::Context::events). Ends once no more
+/// events are queued, or after yielding an error.
+#[derive(Debug)]
+pub struct Events<'a> {
+    context: &'a Context,
+    done: bool,
+}
+
+impl<'a> Events<'a> {
+    pub(crate) fn new(context: &'a Context) -> Self {
+        Self {
+            context,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Events<'_> {
+    type Item = Result<Event, SynthizerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let mut event = unsafe { std::mem::zeroed::<syz_Event>() };
+            let result =
+                wrap!(unsafe { syz_contextGetNextEvent(&mut event, **self.context.handle(), 0) });
+            if let Err(e) = result {
+                self.done = true;
+                return Some(Err(e));
+            }
+            if event.type_ as u32 == SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_INVALID as u32 {
+                self.done = true;
+                return None;
+            }
+            match Event::new(event, self.context) {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Polls a context's events from a background thread, until the context is
+/// dropped, `done` returns `true` before a poll, or `f` returns `false`.
+pub(crate) fn spawn_poller(
+    context: &Context,
+    interval: Duration,
+    done: impl Fn() -> bool + Send + 'static,
+    mut f: impl FnMut(Event) -> bool + Send + 'static,
+) {
+    let context = context.handle().downgrade();
+    thread::spawn(move || {
+        while let Some(context) = context.upgrade() {
+            if done() {
+                return;
+            }
+            let context = Context(context);
+            for event in context.events() {
+                match event {
+                    Ok(event) => {
+                        if !f(event) {
+                            return;
+                        }
+                    }
+                    Err(e) => log::warn!(target: "synthizer", "Error polling events: {}", e),
+                }
+            }
+            drop(context);
+            thread::sleep(interval);
+        }
+    });
+}
+
+/// Events delivered from a background thread.
+///
+/// Created by [`Context::event_receiver`](crate::Context::event_receiver), and
+/// used through the [`Receiver`] it dereferences to. Dropping it stops the
+/// thread at its next poll.
+#[derive(Debug)]
+pub struct EventReceiver {
+    receiver: Receiver<Event>,
+    // The thread stops once this has no strong references left.
+    _alive: Arc<()>,
+}
+
+impl Deref for EventReceiver {
+    type Target = Receiver<Event>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.receiver
+    }
+}
+
+pub(crate) fn receiver(context: &Context, interval: Duration) -> EventReceiver {
+    let (sender, receiver) = channel();
+    let alive = Arc::new(());
+    let watched = Arc::downgrade(&alive);
+    spawn_poller(
+        context,
+        interval,
+        move || watched.strong_count() == 0,
+        move |event| sender.send(event).is_ok(),
+    );
+    EventReceiver {
+        receiver,
+        _alive: alive,
+    }
+}
@@ -0,0 +1,327 @@
+//! Gain fades built on automation.
+//!
+//! Fades are scheduled on the audio thread, so they are sample accurate and
+//! keep going however busy the game loop is. They ramp the gain the
+//! application set, so occlusion and propagation keep applying on top. Each
+//! fade ends with a user automation event identifying it, which
+//! [`Fade::is_complete`] recognizes.
+//!
+//! Pausing after a fade out can't be automated. Instead a background thread
+//! watches each context's clock and pauses objects once their fade out has
+//! ended, so it happens whether or not anything consumes events. The thread
+//! exits once no pauses are pending.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    automation::Target, ffi::*, property, scaled, Context, Event, EventKind, Generator, Handle,
+    SynthizerError, WeakHandle,
+};
+
+// User automation params at or above this are fades. The top bit keeps them
+// clear of params applications pick themselves.
+const FADE_PARAM_BASE: u64 = 1 << 63;
+
+static NEXT_FADE: AtomicU64 = AtomicU64::new(FADE_PARAM_BASE);
+
+// The longest the pausing thread sleeps before checking the clocks again.
+// Headless contexts only move on when rendered, so their clocks can't be
+// predicted.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+struct Pause {
+    param: u64,
+    end_time: f64,
+    target: WeakHandle,
+}
+
+struct Pauses {
+    // The fade each object will pause after, if any. A newer fade on the same
+    // object cancels the pause.
+    pending: BTreeMap<syz_Handle, Pause>,
+    // Whether the thread pausing them is running.
+    running: bool,
+}
+
+static PAUSES: Mutex<Pauses> = Mutex::new(Pauses {
+    pending: BTreeMap::new(),
+    running: false,
+});
+
+/// A scheduled fade.
+#[derive(Clone, Debug)]
+pub struct Fade {
+    target: Handle,
+    param: u64,
+    end_time: f64,
+}
+
+impl Fade {
+    /// When the fade ends, on the context's clock.
+    pub fn end_time(&self) -> f64 {
+        self.end_time
+    }
+
+    /// The param of the user automation event sent when the fade ends.
+    pub fn param(&self) -> u64 {
+        self.param
+    }
+
+    /// Whether `event` reports that this fade ended.
+    pub fn is_complete(&self, event: &Event) -> bool {
+        event.kind == EventKind::UserAutomation(self.param)
+            && **event.source.handle() == *self.target
+    }
+
+    /// Resolves once the fade ends. Events must be consumed through
+    /// [`Context::event_stream`](crate::Context::event_stream) or other
+    /// futures, not polled, for this to see them.
+    #[cfg(feature = "async")]
+    pub fn finished(&self) -> crate::Finished {
+        crate::Finished::when(&self.target, EventKind::UserAutomation(self.param))
+    }
+}
+
+fn context_of(target: &Handle) -> Result<Context, SynthizerError> {
+    target
+        .context()
+        .map(Context)
+        .ok_or_else(|| SynthizerError::InvalidArgument("Object has no live context".to_string()))
+}
+
+/// Ramps `target`'s gain from where it is to `gain` over `duration`,
+/// starting at the context's suggested automation time.
+pub(crate) fn fade_to(
+    target: &Handle,
+    gain: f64,
+    duration: Duration,
+) -> Result<Fade, SynthizerError> {
+    let context = context_of(target)?;
+    let start = context.get_suggested_automation_time()?;
+    let end_time = start + duration.as_secs_f64();
+    let param = NEXT_FADE.fetch_add(1, Ordering::Relaxed);
+    scaled::ramp(target, property::GAIN, start, end_time, gain)?;
+    let mut batch = context.new_automation_batch()?;
+    batch.send_user_event(&Target(target), end_time, param)?;
+    batch.execute()?;
+    PAUSES.lock().unwrap().pending.remove(&**target);
+    Ok(Fade {
+        target: target.clone(),
+        param,
+        end_time,
+    })
+}
+
+/// Fades `target` out, then pauses it from a background thread once its
+/// context's clock passes the end of the fade.
+pub(crate) fn fade_out_and_pause(
+    target: &Handle,
+    duration: Duration,
+) -> Result<Fade, SynthizerError> {
+    let fade = fade_to(target, 0., duration)?;
+    let mut pauses = PAUSES.lock().unwrap();
+    pauses.pending.insert(
+        **target,
+        Pause {
+            param: fade.param,
+            end_time: fade.end_time,
+            target: target.downgrade(),
+        },
+    );
+    if !pauses.running {
+        pauses.running = true;
+        thread::spawn(run_pauses);
+    }
+    Ok(fade)
+}
+
+/// Pauses objects as their fades out end, until none are pending.
+fn run_pauses() {
+    loop {
+        let pending = {
+            let mut pauses = PAUSES.lock().unwrap();
+            pauses.pending.retain(|_, v| v.target.upgrade().is_some());
+            if pauses.pending.is_empty() {
+                pauses.running = false;
+                return;
+            }
+            pauses
+                .pending
+                .values()
+                .filter_map(|v| Some((v.target.upgrade()?, v.param, v.end_time)))
+                .collect::<Vec<_>>()
+        };
+        let mut wait = POLL_INTERVAL;
+        for (target, param, end_time) in pending {
+            // Reading the clock calls into Synthizer, so it's done unlocked.
+            let now = target.context().map(|v| Context(v).get_current_time());
+            let due = match now {
+                Some(Ok(now)) if now < end_time => {
+                    wait = wait.min(Duration::from_secs_f64(end_time - now));
+                    continue;
+                }
+                Some(Ok(_)) => true,
+                // The clock can never reach the end, so give up on it.
+                Some(Err(e)) => {
+                    log::warn!(target: "synthizer", "Error reading time for fade: {}", e);
+                    false
+                }
+                None => false,
+            };
+            // Pausing while locked means a fade started meanwhile either
+            // cancels this first, or starts after the pause.
+            let mut pauses = PAUSES.lock().unwrap();
+            if !pauses
+                .pending
+                .get(&*target)
+                .is_some_and(|v| v.param == param)
+            {
+                continue;
+            }
+            pauses.pending.remove(&*target);
+            if due {
+                if let Err(e) = target.pause() {
+                    log::warn!(target: "synthizer", "Error pausing after fade: {}", e);
+                }
+            }
+        }
+        thread::sleep(wait);
+    }
+}
+
+impl Context {
+    /// Fades `from` out and pauses it while fading `to` in from silence to
+    /// the gain `from` had, starting `to` if it was paused.
+    ///
+    /// `from` pauses from a background thread once the context's clock
+    /// passes the end of the fade, as with
+    /// [`Generator::fade_out_and_pause`].
+    ///
+    /// Returns the fade in, which ends together with the fade out.
+    pub fn crossfade(
+        &self,
+        from: &impl Generator,
+        to: &impl Generator,
+        duration: Duration,
+    ) -> Result<Fade, SynthizerError> {
+        let gain = from.handle().get(property::GAIN)?;
+        fade_out_and_pause(from.handle(), duration)?;
+        to.handle().set(property::GAIN, 0.)?;
+        to.play()?;
+        fade_to(to.handle(), gain, duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock, HeadlessContext, Object, Source, Synthizer};
+
+    #[test]
+    fn fades_gain() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_direct_source()?;
+        source.set_gain(0.5)?;
+        let fade = source.fade_to(1., Duration::from_millis(500))?;
+        let points = mock::automation(**source.handle(), property::GAIN.id());
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].2[0], 0.5);
+        assert_eq!((points[1].0, points[1].2[0]), (fade.end_time(), 1.));
+        assert!(fade.param() >= FADE_PARAM_BASE);
+        assert_eq!(
+            mock::user_events(**source.handle()),
+            [(fade.end_time(), fade.param())]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn recognizes_completion() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        context.enable_events()?;
+        let source = context.new_direct_source()?;
+        let fade = source.fade_to(0.5, Duration::from_millis(10))?;
+        let other = source.fade_to(1., Duration::from_millis(10))?;
+        let user_automation = SYZ_EVENT_TYPES_SYZ_EVENT_TYPE_USER_AUTOMATION;
+        mock::push_event(
+            **context.handle(),
+            user_automation,
+            **source.handle(),
+            other.param(),
+        );
+        mock::push_event(
+            **context.handle(),
+            user_automation,
+            **source.handle(),
+            fade.param(),
+        );
+        let events = context.events().collect::<Result<Vec<_>, _>>()?;
+        assert!(!fade.is_complete(&events[0]));
+        assert!(fade.is_complete(&events[1]));
+        Ok(())
+    }
+
+    /// Renders `context` past the end of every fade scheduled on `object`,
+    /// then waits for any pause after them.
+    fn end_fades(context: &mut HeadlessContext, object: &Handle) -> Result<(), SynthizerError> {
+        let end = mock::user_events(**object)
+            .iter()
+            .fold(0., |end, (time, _)| f64::max(end, *time));
+        while context.get_current_time()? < end {
+            context.render_frames(512)?;
+        }
+        for _ in 0..200 {
+            if !PAUSES.lock().unwrap().pending.contains_key(&**object) {
+                // A check already under way could still pause it.
+                thread::sleep(POLL_INTERVAL * 2);
+                return Ok(());
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        panic!("Fade never ended");
+    }
+
+    #[test]
+    fn pauses_after_fading_out() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let from = context.new_buffer_generator()?;
+        let to = context.new_buffer_generator()?;
+        from.handle().set(property::GAIN, 0.8)?;
+        to.pause()?;
+        context.crossfade(&from, &to, Duration::from_millis(20))?;
+        assert!(to.is_playing());
+        let points = mock::automation(**to.handle(), property::GAIN.id());
+        assert_eq!((points[0].2[0], points[1].2[0]), (0., 0.8));
+        // The clock hasn't moved, so the fade hasn't ended.
+        thread::sleep(POLL_INTERVAL * 2);
+        assert!(from.is_playing());
+        // Nothing enables or drains events.
+        end_fades(&mut context, from.handle())?;
+        assert!(!from.is_playing());
+        assert!(to.is_playing());
+        Ok(())
+    }
+
+    #[test]
+    fn fading_again_cancels_the_pause() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let generator = context.new_buffer_generator()?;
+        generator.fade_out_and_pause(Duration::from_millis(1))?;
+        generator.fade_to(1., Duration::from_millis(1))?;
+        end_fades(&mut context, generator.handle())?;
+        assert!(generator.is_playing());
+        Ok(())
+    }
+}
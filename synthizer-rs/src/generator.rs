@@ -0,0 +1,349 @@
+use std::{path::Path, ptr::null_mut, time::Duration};
+
+use enum_primitive_derive::Primitive;
+
+use crate::{
+    buffer::path_to_cstring, fade, ffi::*, property, Buffer, Context, Fade, Handle, Object,
+    Protocol, SynthizerError,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Primitive)]
+#[repr(i32)]
+pub enum NoiseType {
+    Uniform = SYZ_NOISE_TYPE_SYZ_NOISE_TYPE_UNIFORM as i32,
+    VM = SYZ_NOISE_TYPE_SYZ_NOISE_TYPE_VM as i32,
+    FilteredBrown = SYZ_NOISE_TYPE_SYZ_NOISE_TYPE_FILTERED_BROWN as i32,
+    Count = SYZ_NOISE_TYPE_SYZ_NOISE_TYPE_COUNT as i32,
+}
+
+/// Something that produces audio for sources to play.
+///
+/// Only Synthizer's own generators implement this. Synthizer 0.11 has no API
+/// for generators implemented outside it, so audio produced in Rust has to be
+/// handed over as a buffer or a stream instead.
+pub trait Generator: Object {
+    /// Pauses the generator where it is.
+    fn pause(&self) -> Result<(), SynthizerError> {
+        self.handle().pause()
+    }
+
+    /// Resumes a paused generator from where it left off.
+    fn play(&self) -> Result<(), SynthizerError> {
+        self.handle().play()
+    }
+
+    /// Whether the generator is playing rather than paused.
+    fn is_playing(&self) -> bool {
+        self.handle().is_playing()
+    }
+
+    /// Ramps the gain to `gain` over `duration`, replacing any fade in
+    /// progress.
+    fn fade_to(&self, gain: f64, duration: Duration) -> Result<Fade, SynthizerError> {
+        fade::fade_to(self.handle(), gain, duration)
+    }
+
+    /// Fades the gain to 0 over `duration`, then pauses once the context's
+    /// clock passes the fade's end. Fading again first cancels the pause.
+    ///
+    /// The pause comes from a background thread and doesn't need events, so
+    /// it happens even if the context's events are never enabled or drained.
+    /// Headless contexts only pause once rendered past the fade.
+    fn fade_out_and_pause(&self, duration: Duration) -> Result<Fade, SynthizerError> {
+        fade::fade_out_and_pause(self.handle(), duration)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StreamingGenerator(pub(crate) Handle);
+
+impl StreamingGenerator {
+    pub(crate) fn new<S: Into<String>>(
+        context: &Context,
+        protocol: Protocol,
+        path: &Path,
+        _options: S,
+    ) -> Result<Self, SynthizerError> {
+        let mut handle = 0;
+        let protocol = protocol.to_cstring()?;
+        let path = path_to_cstring(path)?;
+        wrap!(
+            unsafe {
+                syz_createStreamingGeneratorFromStreamParams(
+                    &mut handle,
+                    **context,
+                    protocol.as_ptr(),
+                    path.as_ptr(),
+                    null_mut(),
+                    null_mut(),
+                    null_mut(),
+                    None,
+                )
+            },
+            Self(Handle::new_in(handle, context.handle()))
+        )
+    }
+
+    property!(pub playback_position: f64 = property::PLAYBACK_POSITION);
+    property!(pub looping: bool = property::LOOPING);
+
+    /// Resolves once this generator reaches its end without looping.
+    ///
+    /// Only finishing after this is called counts, so call it before the
+    /// generator can reach its end.
+    #[cfg(feature = "async")]
+    pub fn finished(&self) -> crate::Finished {
+        crate::Finished::new(self.handle())
+    }
+}
+
+make_object!(StreamingGenerator);
+
+impl Generator for StreamingGenerator {}
+
+#[derive(Clone, Debug)]
+pub struct BufferGenerator(pub(crate) Handle);
+
+impl BufferGenerator {
+    pub(crate) fn new(context: &Context) -> Result<Self, SynthizerError> {
+        let mut handle = 0;
+        wrap!(
+            unsafe {
+                syz_createBufferGenerator(&mut handle, **context, null_mut(), null_mut(), None)
+            },
+            Self(Handle::new_in(handle, context.handle()))
+        )
+    }
+
+    pub fn get_buffer(&self) -> Result<Option<Buffer>, SynthizerError> {
+        self.handle().get(property::BUFFER)
+    }
+
+    pub fn set_buffer(&self, buffer: Buffer) -> Result<(), SynthizerError> {
+        self.handle().set(property::BUFFER, Some(buffer))
+    }
+
+    property!(pub playback_position: f64 = property::PLAYBACK_POSITION);
+    property!(pub looping: bool = property::LOOPING);
+
+    property!(pub pitch_bend: f64 = property::PITCH_BEND);
+
+    /// Resolves once this generator reaches its end without looping.
+    ///
+    /// Only finishing after this is called counts, so call it before the
+    /// generator can reach its end.
+    #[cfg(feature = "async")]
+    pub fn finished(&self) -> crate::Finished {
+        crate::Finished::new(self.handle())
+    }
+}
+
+make_object!(BufferGenerator);
+
+impl Generator for BufferGenerator {}
+
+#[derive(Clone, Debug)]
+pub struct NoiseGenerator(pub(crate) Handle);
+
+impl NoiseGenerator {
+    pub(crate) fn new(context: &Context, channels: u32) -> Result<Self, SynthizerError> {
+        let mut handle = 0;
+        wrap!(
+            unsafe {
+                syz_createNoiseGenerator(
+                    &mut handle,
+                    **context,
+                    channels,
+                    null_mut(),
+                    null_mut(),
+                    None,
+                )
+            },
+            Self(Handle::new_in(handle, context.handle()))
+        )
+    }
+
+    property!(pub noise_type: NoiseType = property::NOISE_TYPE);
+}
+
+make_object!(NoiseGenerator);
+
+impl Generator for NoiseGenerator {}
+
+/// One sine wave in a [`FastSineBankGenerator`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SineBankWave {
+    /// The wave's frequency as a multiple of the generator's.
+    pub frequency_mul: f64,
+    /// The wave's phase, from 0 to 1.
+    pub phase: f64,
+    pub gain: f64,
+}
+
+impl SineBankWave {
+    pub fn new(frequency_mul: f64, phase: f64, gain: f64) -> Self {
+        Self {
+            frequency_mul,
+            phase,
+            gain,
+        }
+    }
+}
+
+/// A waveform built from sine waves, which Synthizer can approximate with
+/// a given number of partials.
+///
+/// More partials sound closer to the real waveform but cost more to play.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Waveform {
+    Sine,
+    Square(u32),
+    Triangle(u32),
+    Sawtooth(u32),
+}
+
+/// Plays a bank of sine waves at multiples of one frequency, for tones and
+/// beeps that don't need a sound file.
+#[derive(Clone, Debug)]
+pub struct FastSineBankGenerator(pub(crate) Handle);
+
+impl FastSineBankGenerator {
+    pub(crate) fn new(
+        context: &Context,
+        initial_frequency: f64,
+        waves: &[SineBankWave],
+    ) -> Result<Self, SynthizerError> {
+        let waves = waves
+            .iter()
+            .map(|v| syz_SineBankWave {
+                frequency_mul: v.frequency_mul,
+                phase: v.phase,
+                gain: v.gain,
+            })
+            .collect::<Vec<_>>();
+        let config = syz_SineBankConfig {
+            waves: waves.as_ptr(),
+            wave_count: waves.len() as u64,
+            initial_frequency,
+        };
+        let mut handle = 0;
+        wrap!(
+            unsafe {
+                syz_createFastSineBankGenerator(
+                    &mut handle,
+                    **context,
+                    &config,
+                    null_mut(),
+                    null_mut(),
+                    None,
+                )
+            },
+            Self(Handle::new_in(handle, context.handle()))
+        )
+    }
+
+    pub(crate) fn from_waveform(
+        context: &Context,
+        waveform: Waveform,
+        initial_frequency: f64,
+    ) -> Result<Self, SynthizerError> {
+        if let Waveform::Square(0) | Waveform::Triangle(0) | Waveform::Sawtooth(0) = waveform {
+            return Err(SynthizerError::InvalidArgument(format!(
+                "{:?} needs at least one partial",
+                waveform
+            )));
+        }
+        let mut handle = 0;
+        let (context_handle, f) = (**context, initial_frequency);
+        let result = unsafe {
+            match waveform {
+                Waveform::Sine => syz_createFastSineBankGeneratorSine(
+                    &mut handle,
+                    context_handle,
+                    f,
+                    null_mut(),
+                    null_mut(),
+                    None,
+                ),
+                Waveform::Square(partials) => syz_createFastSineBankGeneratorSquare(
+                    &mut handle,
+                    context_handle,
+                    f,
+                    partials,
+                    null_mut(),
+                    null_mut(),
+                    None,
+                ),
+                Waveform::Triangle(partials) => syz_createFastSineBankGeneratorTriangle(
+                    &mut handle,
+                    context_handle,
+                    f,
+                    partials,
+                    null_mut(),
+                    null_mut(),
+                    None,
+                ),
+                Waveform::Sawtooth(partials) => syz_createFastSineBankGeneratorSaw(
+                    &mut handle,
+                    context_handle,
+                    f,
+                    partials,
+                    null_mut(),
+                    null_mut(),
+                    None,
+                ),
+            }
+        };
+        wrap!(result, Self(Handle::new_in(handle, context.handle())))
+    }
+
+    property!(pub frequency: f64 = property::FREQUENCY);
+}
+
+make_object!(FastSineBankGenerator);
+
+impl Generator for FastSineBankGenerator {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock, Synthizer};
+
+    #[test]
+    fn creates_sine_banks() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let waves = [
+            SineBankWave::new(1., 0., 0.5),
+            SineBankWave::new(3., 0.25, 0.1),
+        ];
+        let generator = context.new_fast_sine_bank_generator(440., &waves)?;
+        assert_eq!(generator.get_frequency()?, 440.);
+        assert_eq!(mock::sine_bank_waves(**generator.handle()), waves);
+        generator.set_frequency(880.)?;
+        assert_eq!(generator.get_frequency()?, 880.);
+        Ok(())
+    }
+
+    #[test]
+    fn creates_waveforms() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let sine = context.new_waveform_generator(Waveform::Sine, 220.)?;
+        assert_eq!(mock::sine_bank_waves(**sine.handle()).len(), 1);
+        let square = context.new_waveform_generator(Waveform::Square(10), 220.)?;
+        assert_eq!(square.get_frequency()?, 220.);
+        assert_eq!(mock::sine_bank_waves(**square.handle()).len(), 10);
+        for waveform in [
+            Waveform::Square(0),
+            Waveform::Triangle(0),
+            Waveform::Sawtooth(0),
+        ]
+        .iter()
+        {
+            let error = context.new_waveform_generator(*waveform, 220.).unwrap_err();
+            assert!(matches!(error, SynthizerError::InvalidArgument(_)));
+        }
+        Ok(())
+    }
+}
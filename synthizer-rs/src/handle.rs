@@ -0,0 +1,319 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Deref,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
+use crate::{
+    ffi::*, property::Property, property::PropertyValue, CoordinateSystem, Library, SynthizerError,
+};
+
+#[derive(Debug)]
+struct Inner {
+    handle: syz_Handle,
+    // Synthizer can't read object properties back, so we remember what we
+    // set them to.
+    objects: Mutex<HashMap<i32, Handle>>,
+    // The context this object was created in, if any.
+    context: Option<WeakHandle>,
+    // Synthizer can't tell us whether an object is paused either.
+    paused: AtomicBool,
+    // Only used by contexts, whose objects look it up through `context`.
+    coordinates: Mutex<CoordinateSystem>,
+    // Dropped after the handle is released, so Synthizer outlives it.
+    library: Arc<Library>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        {
+            let mut live = LIVE.lock().unwrap();
+            // A borrowed handle may have replaced us while we were dropping.
+            if let Some(v) = live.get(&self.handle) {
+                if std::ptr::eq(v.as_ptr(), self) {
+                    live.remove(&self.handle);
+                }
+            }
+        }
+        unsafe { syz_handleDecRef(self.handle) };
+    }
+}
+
+// Every handle we hold a reference to, so handles Synthizer hands back to us
+// map to the same `Handle`, with the same remembered object properties.
+static LIVE: Mutex<BTreeMap<syz_Handle, Weak<Inner>>> = Mutex::new(BTreeMap::new());
+
+/// An owned reference to a Synthizer object.
+///
+/// Clones share a single native reference, which is released when the last
+/// clone is dropped.
+#[derive(Clone, Debug)]
+pub struct Handle(Arc<Inner>);
+
+impl Handle {
+    /// Takes ownership of a reference Synthizer handed us, usually from a
+    /// constructor, keeping `library` initialized until it's released.
+    pub(crate) fn new(handle: syz_Handle, library: Arc<Library>) -> Self {
+        Self::with_context(handle, None, library)
+    }
+
+    /// Like `new`, for objects created in a context, which keep the
+    /// context's library.
+    pub(crate) fn new_in(handle: syz_Handle, context: &Handle) -> Self {
+        let library = context.0.library.clone();
+        Self::with_context(handle, Some(context.downgrade()), library)
+    }
+
+    fn with_context(
+        handle: syz_Handle,
+        context: Option<WeakHandle>,
+        library: Arc<Library>,
+    ) -> Self {
+        let inner = Arc::new(Inner {
+            handle,
+            objects: Default::default(),
+            context,
+            paused: AtomicBool::new(false),
+            coordinates: Default::default(),
+            library,
+        });
+        LIVE.lock().unwrap().insert(handle, Arc::downgrade(&inner));
+        Self(inner)
+    }
+
+    /// Acquires a reference to a handle in `context` owned elsewhere, sharing
+    /// an existing `Handle` if we already have one.
+    pub(crate) fn from_borrowed(
+        handle: syz_Handle,
+        context: &Handle,
+    ) -> Result<Self, SynthizerError> {
+        if let Some(inner) = LIVE.lock().unwrap().get(&handle).and_then(|v| v.upgrade()) {
+            return Ok(Self(inner));
+        }
+        wrap!(
+            unsafe { syz_handleIncRef(handle) },
+            Self::new_in(handle, context)
+        )
+    }
+
+    #[cfg(test)]
+    pub(crate) fn library_is(&self, library: &Arc<Library>) -> bool {
+        Arc::ptr_eq(&self.0.library, library)
+    }
+
+    /// The context this object was created in, if it's still alive.
+    pub(crate) fn context(&self) -> Option<Handle> {
+        self.0.context.as_ref().and_then(|v| v.upgrade())
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakHandle {
+        WeakHandle(Arc::downgrade(&self.0))
+    }
+
+    pub(crate) fn object_type(&self) -> Result<i32, SynthizerError> {
+        let mut out = 0;
+        wrap!(
+            unsafe { syz_handleGetObjectType(&mut out, **self) },
+            out as i32
+        )
+    }
+
+    pub fn get<T: PropertyValue>(&self, property: Property<T>) -> Result<T, SynthizerError> {
+        T::get(self, property.id())
+    }
+
+    pub fn set<T: PropertyValue>(
+        &self,
+        property: Property<T>,
+        value: T,
+    ) -> Result<(), SynthizerError> {
+        value.set(self, property.id())
+    }
+
+    pub(crate) fn pause(&self) -> Result<(), SynthizerError> {
+        wrap!(unsafe { syz_pause(**self) })?;
+        self.0.paused.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub(crate) fn play(&self) -> Result<(), SynthizerError> {
+        wrap!(unsafe { syz_play(**self) })?;
+        self.0.paused.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Whether the object is playing, as far as pausing it goes. A playing
+    /// generator may still have nothing left to play.
+    pub(crate) fn is_playing(&self) -> bool {
+        !self.0.paused.load(Ordering::SeqCst)
+    }
+
+    /// The coordinate system of this object's context, or of this object if
+    /// it's a context. Objects outliving their context fall back to
+    /// Synthizer's, as they can't be heard anyway.
+    pub(crate) fn coordinate_system(&self) -> CoordinateSystem {
+        let context = self.context().unwrap_or_else(|| self.clone());
+        let system = *context.0.coordinates.lock().unwrap();
+        system
+    }
+
+    pub(crate) fn set_coordinate_system(&self, system: CoordinateSystem) {
+        *self.0.coordinates.lock().unwrap() = system;
+    }
+
+    pub(crate) fn get_object(&self, property: i32) -> Option<Handle> {
+        self.0.objects.lock().unwrap().get(&property).cloned()
+    }
+
+    pub(crate) fn set_object(
+        &self,
+        property: i32,
+        value: Option<Handle>,
+    ) -> Result<(), SynthizerError> {
+        let raw = value.as_ref().map(|v| **v).unwrap_or(0);
+        wrap!(unsafe { syz_setO(**self, property, raw) })?;
+        let mut objects = self.0.objects.lock().unwrap();
+        if let Some(value) = value {
+            objects.insert(property, value);
+        } else {
+            objects.remove(&property);
+        }
+        Ok(())
+    }
+}
+
+/// A reference to a handle which doesn't keep it alive.
+#[derive(Clone, Debug)]
+pub(crate) struct WeakHandle(Weak<Inner>);
+
+impl WeakHandle {
+    pub(crate) fn upgrade(&self) -> Option<Handle> {
+        self.0.upgrade().map(Handle)
+    }
+
+    /// Whether this refers to the same object as `handle`.
+    pub(crate) fn is(&self, handle: &Handle) -> bool {
+        self.0.as_ptr() == Arc::as_ptr(&handle.0)
+    }
+}
+
+impl Deref for Handle {
+    type Target = syz_Handle;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0.handle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock, property, Buffer, Object, Protocol, Source, Synthizer};
+
+    fn buffer() -> Result<Buffer, SynthizerError> {
+        Buffer::new(Protocol::File, "test.wav".as_ref(), "")
+    }
+
+    #[test]
+    fn clones_share_one_reference() -> Result<(), SynthizerError> {
+        let _synthizer = Synthizer::new()?;
+        let buffer = buffer()?;
+        let raw = **buffer.handle();
+        let clone = buffer.clone();
+        assert_eq!(mock::refcount(raw), Some(1));
+        drop(buffer);
+        assert_eq!(mock::refcount(raw), Some(1));
+        drop(clone);
+        assert_eq!(mock::refcount(raw), None);
+        assert_eq!(mock::double_frees(raw), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn borrowed_handles_take_their_own_reference() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let context = synthizer.new_context()?;
+        // A reference held outside this crate, as in an event.
+        let mut raw = 0;
+        wrap!(unsafe {
+            syz_createBufferFromFloatArray(
+                &mut raw,
+                44100,
+                1,
+                0,
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                None,
+            )
+        })?;
+        let borrowed = Handle::from_borrowed(raw, context.handle())?;
+        assert_eq!(mock::refcount(raw), Some(2));
+        unsafe { syz_handleDecRef(raw) };
+        assert_eq!(mock::refcount(raw), Some(1));
+        drop(borrowed);
+        assert_eq!(mock::refcount(raw), None);
+        assert_eq!(mock::double_frees(raw), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn borrowing_a_live_handle_shares_it() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let generator = context.new_buffer_generator()?;
+        let raw = **generator.handle();
+        generator.set_buffer(buffer()?)?;
+        let borrowed = Handle::from_borrowed(raw, context.handle())?;
+        assert_eq!(mock::refcount(raw), Some(1));
+        assert!(borrowed.get_object(property::BUFFER.id()).is_some());
+        drop(generator);
+        drop(borrowed);
+        assert_eq!(mock::refcount(raw), None);
+        Ok(())
+    }
+
+    #[test]
+    fn object_getter_does_not_steal_parent_reference() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let generator = context.new_buffer_generator()?;
+        let buffer = buffer()?;
+        let raw = **buffer.handle();
+        generator.set_buffer(buffer)?;
+        let fetched = generator.get_buffer()?.unwrap();
+        drop(fetched);
+        assert_eq!(mock::refcount(raw), Some(1));
+        let fetched = generator.get_buffer()?.unwrap();
+        assert_eq!(**fetched.handle(), raw);
+        drop(generator);
+        assert_eq!(mock::refcount(raw), Some(1));
+        drop(fetched);
+        assert_eq!(mock::refcount(raw), None);
+        assert_eq!(mock::double_frees(raw), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn objects_outliving_their_context() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_direct_source()?;
+        let generator = context.new_buffer_generator()?;
+        source.add_generator(&generator)?;
+        let handles = [**context.handle(), **source.handle(), **generator.handle()];
+        let clone = source.clone();
+        drop(context);
+        drop(source);
+        clone.set_gain(0.5)?;
+        drop(generator);
+        drop(clone);
+        for handle in handles.iter() {
+            assert_eq!(mock::refcount(*handle), None);
+            assert_eq!(mock::double_frees(*handle), 0);
+        }
+        Ok(())
+    }
+}
@@ -1,15 +1,26 @@
-use std::{ffi::CString, ops::Deref, ops::DerefMut, path::Path, ptr::null_mut, time::Duration};
+// bindgen gives Synthizer's enums a different integer type per platform, so
+// casts which are no-ops here aren't everywhere.
+#![allow(clippy::unnecessary_cast)]
 
-use enum_primitive_derive::Primitive;
-use log::Level;
-use num_traits::ToPrimitive;
-use paste::paste;
-use synthizer_sys::*;
-use thiserror::Error;
+use std::{
+    ptr::null,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
 
-#[derive(Clone, Debug, Error)]
-#[error("Synthizer error: {0}")]
-pub struct SynthizerError(syz_ErrorCode);
+use log::{Level, LevelFilter};
+
+#[cfg(not(test))]
+use synthizer_sys as ffi;
+
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+use mock as ffi;
+
+use ffi::*;
 
 macro_rules! wrap {
     ($call:expr) => {{
@@ -17,7 +28,7 @@ macro_rules! wrap {
         if v == 0 {
             Ok(())
         } else {
-            Err(SynthizerError(v))
+            Err(SynthizerError::last(v))
         }
     }};
     ($call:expr, $rv:expr) => {{
@@ -25,305 +36,52 @@ macro_rules! wrap {
         if v == 0 {
             Ok($rv)
         } else {
-            Err(SynthizerError(v))
+            Err(SynthizerError::last(v))
         }
     }};
 }
 
-pub enum LoggingBackend {
-    Stderr = SYZ_LOGGING_BACKEND_SYZ_LOGGING_BACKEND_STDERR as isize,
-}
-
-pub fn configure_logging_backend(backend: LoggingBackend) -> Result<(), SynthizerError> {
-    let backend = match backend {
-        LoggingBackend::Stderr => SYZ_LOGGING_BACKEND_SYZ_LOGGING_BACKEND_STDERR,
-    };
-    let param = null_mut();
-    wrap!(unsafe { syz_configureLoggingBackend(backend, param) })
-}
-
-pub fn set_log_level(level: Level) {
-    let level = match level {
-        Level::Error => SYZ_LOG_LEVEL_SYZ_LOG_LEVEL_ERROR,
-        Level::Warn => SYZ_LOG_LEVEL_SYZ_LOG_LEVEL_WARN,
-        Level::Info => SYZ_LOG_LEVEL_SYZ_LOG_LEVEL_INFO,
-        Level::Debug => SYZ_LOG_LEVEL_SYZ_LOG_LEVEL_DEBUG,
-        _ => panic!("Level not supported"),
-    };
-    unsafe { syz_setLogLevel(level) };
-}
-
-fn initialize() -> Result<(), SynthizerError> {
-    wrap!(unsafe { syz_initialize() })
-}
-
-fn shutdown() -> Result<(), SynthizerError> {
-    wrap!(unsafe { syz_shutdown() })
-}
-
-#[derive(Clone, Debug)]
-pub struct Handle(syz_Handle);
-
-impl Deref for Handle {
-    type Target = syz_Handle;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl DerefMut for Handle {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-
-#[derive(Primitive)]
-#[repr(i32)]
-enum Property {
-    Azimuth = SYZ_PROPERTIES_SYZ_P_AZIMUTH,
-    Buffer = SYZ_PROPERTIES_SYZ_P_BUFFER,
-    ClosenessBoost = SYZ_PROPERTIES_SYZ_P_CLOSENESS_BOOST,
-    ClosenessBoostDistance = SYZ_PROPERTIES_SYZ_P_CLOSENESS_BOOST_DISTANCE,
-    DistanceMax = SYZ_PROPERTIES_SYZ_P_DISTANCE_MAX,
-    DistanceModel = SYZ_PROPERTIES_SYZ_P_DISTANCE_MODEL,
-    DistanceRef = SYZ_PROPERTIES_SYZ_P_DISTANCE_REF,
-    Elevation = SYZ_PROPERTIES_SYZ_P_ELEVATION,
-    Gain = SYZ_PROPERTIES_SYZ_P_GAIN,
-    PannerStrategy = SYZ_PROPERTIES_SYZ_P_PANNER_STRATEGY,
-    PanningScalar = SYZ_PROPERTIES_SYZ_P_PANNING_SCALAR,
-    Position = SYZ_PROPERTIES_SYZ_P_POSITION,
-    Orientation = SYZ_PROPERTIES_SYZ_P_ORIENTATION,
-    Rolloff = SYZ_PROPERTIES_SYZ_P_ROLLOFF,
-    Looping = SYZ_PROPERTIES_SYZ_P_LOOPING,
-    NoiseType = SYZ_PROPERTIES_SYZ_P_NOISE_TYPE,
-    PitchBend = SYZ_PROPERTIES_SYZ_P_PITCH_BEND,
-}
-
-#[derive(Primitive)]
-#[repr(i32)]
-pub enum PannerStrategy {
-    HRTF = SYZ_PANNER_STRATEGY_SYZ_PANNER_STRATEGY_HRTF,
-    Stereo = SYZ_PANNER_STRATEGY_SYZ_PANNER_STRATEGY_STEREO,
-    Count = SYZ_PANNER_STRATEGY_SYZ_PANNER_STRATEGY_COUNT,
-}
-
-#[derive(Primitive)]
-#[repr(i32)]
-pub enum DistanceModel {
-    None = SYZ_DISTANCE_MODEL_SYZ_DISTANCE_MODEL_NONE,
-    Linear = SYZ_DISTANCE_MODEL_SYZ_DISTANCE_MODEL_LINEAR,
-    Exponential = SYZ_DISTANCE_MODEL_SYZ_DISTANCE_MODEL_EXPONENTIAL,
-    Inverse = SYZ_DISTANCE_MODEL_SYZ_DISTANCE_MODEL_INVERSE,
-    Count = SYZ_DISTANCE_MODEL_SYZ_DISTANCE_MODEL_COUNT,
-}
-
-#[derive(Primitive)]
-#[repr(i32)]
-pub enum NoiseType {
-    Uniform = SYZ_NOISE_TYPE_SYZ_NOISE_TYPE_UNIFORM,
-    VM = SYZ_NOISE_TYPE_SYZ_NOISE_TYPE_VM,
-    FilteredBrown = SYZ_NOISE_TYPE_SYZ_NOISE_TYPE_FILTERED_BROWN,
-    Count = SYZ_NOISE_TYPE_SYZ_NOISE_TYPE_COUNT,
-}
-
-impl Handle {
-    fn get_i(&self, property: i32) -> Result<i32, SynthizerError> {
-        let out: *mut i32 = null_mut();
-        wrap!(unsafe { syz_getI(out, self.0, property) }, out as i32)
-    }
-
-    fn set_i(&self, property: i32, value: i32) -> Result<(), SynthizerError> {
-        wrap!(unsafe { syz_setI(self.0, property, value) })
-    }
-
-    fn get_d(&self, property: i32) -> Result<*mut f64, SynthizerError> {
-        let out: *mut f64 = null_mut();
-        wrap!(unsafe { syz_getD(out, self.0, property) }, out)
-    }
-
-    fn set_d(&self, property: i32, value: f64) -> Result<(), SynthizerError> {
-        wrap!(unsafe { syz_setD(self.0, property, value) })
-    }
-
-    fn get_o(&self, property: i32) -> Result<Handle, SynthizerError> {
-        let out: *mut u64 = null_mut();
-        wrap!(
-            unsafe { syz_getO(out, self.0, property) },
-            Handle(out as u64)
-        )
-    }
-
-    fn set_o(&self, property: i32, value: Handle) -> Result<(), SynthizerError> {
-        wrap!(unsafe { syz_setO(self.0, property, value.0) })
-    }
-
-    fn get_d3(&self, property: i32) -> Result<(*mut f64, *mut f64, *mut f64), SynthizerError> {
-        let x: *mut f64 = null_mut();
-        let y: *mut f64 = null_mut();
-        let z: *mut f64 = null_mut();
-        wrap!(unsafe { syz_getD3(x, y, z, self.0, property) }, (x, y, z))
-    }
-
-    fn set_d3(&self, property: i32, x: f64, y: f64, z: f64) -> Result<(), SynthizerError> {
-        wrap!(unsafe { syz_setD3(self.0, property, x, y, z) })
-    }
-
-    fn get_d6(
-        &self,
-        property: i32,
-    ) -> Result<(*mut f64, *mut f64, *mut f64, *mut f64, *mut f64, *mut f64), SynthizerError> {
-        let x1: *mut f64 = null_mut();
-        let y1: *mut f64 = null_mut();
-        let z1: *mut f64 = null_mut();
-        let x2: *mut f64 = null_mut();
-        let y2: *mut f64 = null_mut();
-        let z2: *mut f64 = null_mut();
-        wrap!(
-            unsafe { syz_getD6(x1, y1, z1, x2, y2, z2, self.0, property) },
-            (x1, y1, z1, x2, y2, z2)
-        )
-    }
-
-    #[allow(clippy::too_many_arguments)]
-    fn set_d6(
-        &self,
-        property: i32,
-        x1: f64,
-        y1: f64,
-        z1: f64,
-        x2: f64,
-        y2: f64,
-        z2: f64,
-    ) -> Result<(), SynthizerError> {
-        wrap!(unsafe { syz_setD6(self.0, property, x1, y1, z1, x2, y2, z2) })
-    }
-}
-
-impl Drop for Handle {
-    fn drop(&mut self) {
-        unsafe { syz_handleFree(self.0) };
-    }
-}
-
-unsafe impl Send for Handle {}
-
-unsafe impl Sync for Handle {}
-
-macro_rules! i {
-    ($name:ident, $property:path) => {
-        paste! {
-            pub fn [<get_ $name>](&self) -> Result<i32, SynthizerError> {
-                self.handle().get_i($property.to_i32().unwrap())
+// Doc comments before the name document both accessors.
+macro_rules! property {
+    ($(#[$meta:meta])* $vis:vis $name:ident: $type:ty = $property:path) => {
+        paste::paste! {
+            $(#[$meta])*
+            $vis fn [<get_ $name>](&self) -> Result<$type, SynthizerError> {
+                self.handle().get($property)
             }
 
-            pub fn [<set_ $name>](&self, value: i32) -> Result<(), SynthizerError> {
-                self.handle().set_i($property.to_i32().unwrap(), value)
-            }
-        }
-    };
-}
-
-macro_rules! ti {
-    ($name:ident, $property:path) => {
-        paste! {
-            fn [<get_ $name>](&self) -> Result<i32, SynthizerError> {
-                self.handle().get_i($property.to_i32().unwrap())
-            }
-
-            fn [<set_ $name>](&self, value: i32) -> Result<(), SynthizerError> {
-                self.handle().set_i($property.to_i32().unwrap(), value)
-            }
-        }
-    };
-}
-
-macro_rules! d {
-    ($name:ident, $property:path) => {
-        paste! {
-            pub fn [<get_ $name>](&self) -> Result<f64, SynthizerError> {
-                let out = self.handle().get_d($property.to_i32().unwrap())?;
-                let out = unsafe { out.as_ref() };
-                let out = out.cloned();
-                Ok(out.unwrap())
-            }
-
-            pub fn [<set_ $name>](&self, value: f64) -> Result<(), SynthizerError> {
-                self.handle().set_d($property.to_i32().unwrap(), value)
-            }
-        }
-    };
-}
-
-macro_rules! td {
-    ($name:ident, $property:path) => {
-        paste! {
-            fn [<get_ $name>](&self) -> Result<f64, SynthizerError> {
-                let out = self.handle().get_d($property.to_i32().unwrap())?;
-                let out = unsafe { out.as_ref() };
-                let out = out.cloned();
-                Ok(out.unwrap())
-            }
-
-            fn [<set_ $name>](&self, value: f64) -> Result<(), SynthizerError> {
-                self.handle().set_d($property.to_i32().unwrap(), value)
+            $(#[$meta])*
+            $vis fn [<set_ $name>](&self, value: $type) -> Result<(), SynthizerError> {
+                self.handle().set($property, value)
             }
         }
     };
 }
 
+// Vectors are in the coordinate system of the object's context.
 macro_rules! d3 {
-    ($name:ident, $property:path) => {
-        paste! {
-            pub fn [<get_ $name>](&self) -> Result<(f64, f64, f64), SynthizerError> {
-                let (x, y, z) = self.handle().get_d3($property.to_i32().unwrap())?;
-                let x = unsafe { x.as_ref() };
-                let x = x.cloned();
-                let y = unsafe { y.as_ref() };
-                let y = y.cloned();
-                let z = unsafe { z.as_ref() };
-                let z = z.cloned();
-                Ok((x.unwrap(), y.unwrap(), z.unwrap()))
+    ($vis:vis $name:ident = $property:path) => {
+        paste::paste! {
+            $vis fn [<get_ $name>](&self) -> Result<(f64, f64, f64), SynthizerError> {
+                self.handle().get($property)
             }
 
-            pub fn [<set_ $name>](&self, x: f64, y: f64, z: f64) -> Result<(), SynthizerError> {
-                self.handle()
-                    .set_d3(Property::Position.to_i32().unwrap(), x, y, z)
+            $vis fn [<set_ $name>](&self, x: f64, y: f64, z: f64) -> Result<(), SynthizerError> {
+                self.handle().set($property, (x, y, z))
             }
         }
     };
 }
 
 macro_rules! d6 {
-    ($name:ident, $property:path) => {
-        paste! {
-            pub fn [<get_ $name>](&self) -> Result<(f64, f64, f64, f64, f64, f64), SynthizerError> {
-                let (x1, y1, z1, x2, y2, z2) = self
-                    .handle()
-                    .get_d6($property.to_i32().unwrap())?;
-                let x1 = unsafe { x1.as_ref() };
-                let x1 = x1.cloned();
-                let y1 = unsafe { y1.as_ref() };
-                let y1 = y1.cloned();
-                let z1 = unsafe { z1.as_ref() };
-                let z1 = z1.cloned();
-                let x2 = unsafe { x2.as_ref() };
-                let x2 = x2.cloned();
-                let y2 = unsafe { y2.as_ref() };
-                let y2 = y2.cloned();
-                let z2 = unsafe { z2.as_ref() };
-                let z2 = z2.cloned();
-                Ok((
-                    x1.unwrap(),
-                    y1.unwrap(),
-                    z1.unwrap(),
-                    x2.unwrap(),
-                    y2.unwrap(),
-                    z2.unwrap(),
-                ))
+    ($vis:vis $name:ident = $property:path) => {
+        paste::paste! {
+            $vis fn [<get_ $name>](&self) -> Result<(f64, f64, f64, f64, f64, f64), SynthizerError> {
+                self.handle().get($property)
             }
 
-            pub fn [<set_ $name>](
+            #[allow(clippy::too_many_arguments)]
+            $vis fn [<set_ $name>](
                 &self,
                 x1: f64,
                 y1: f64,
@@ -332,376 +90,355 @@ macro_rules! d6 {
                 y2: f64,
                 z2: f64,
             ) -> Result<(), SynthizerError> {
-                self.handle().set_d6(
-                    $property.to_i32().unwrap(),
-                    x1,
-                    y1,
-                    z1,
-                    x2,
-                    y2,
-                    z2,
-                )
+                self.handle().set($property, (x1, y1, z1, x2, y2, z2))
             }
-
         }
     };
 }
 
-pub enum Protocol {
-    File,
-}
-
-#[derive(Clone, Debug)]
-pub struct Buffer(Handle);
-
-impl Buffer {
-    pub fn new<S: Into<String>>(
-        protocol: Protocol,
-        path: &Path,
-        options: S,
-    ) -> Result<Self, SynthizerError> {
-        let mut handle = Handle(0);
-        let protocol = match protocol {
-            Protocol::File => String::from("file"),
-        };
-        let protocol = CString::new(protocol.as_bytes()).expect("Unable to create C string");
-        let protocol = protocol.as_ptr() as *const i8;
-        let path = path.as_os_str().to_string_lossy();
-        let path = CString::new(path.as_bytes()).expect("Unable to create C string");
-        let path = path.as_ptr() as *const i8;
-        let options = options.into();
-        let options = CString::new(options.as_bytes()).expect("Unable to create C string");
-        let options = options.as_ptr() as *const i8;
-        wrap!(
-            unsafe { syz_createBufferFromStream(&mut *handle, protocol, path, options) },
-            Self(handle)
-        )
-    }
-
-    pub fn get_channels(&self) -> Result<u32, SynthizerError> {
-        let out: *mut u32 = null_mut();
-        wrap!(unsafe { syz_bufferGetChannels(out, *self.0) }, out as u32)
-    }
-
-    pub fn get_length_in_samples(&self) -> Result<u32, SynthizerError> {
-        let out: *mut u32 = null_mut();
-        wrap!(
-            unsafe { syz_bufferGetLengthInSamples(out, *self.0) },
-            out as u32
-        )
-    }
+macro_rules! make_object {
+    ($object:ident) => {
+        impl crate::Object for $object {
+            fn handle(&self) -> &crate::Handle {
+                &self.0
+            }
+        }
 
-    pub fn get_length_in_seconds(&self) -> Result<f64, SynthizerError> {
-        let out: *mut f64 = null_mut();
-        wrap!(unsafe { syz_bufferGetLengthInSeconds(out, *self.0) }, {
-            let out = unsafe { out.as_ref() };
-            let out = out.cloned();
-            out.unwrap()
-        })
-    }
+        unsafe impl Send for $object {}
 
-    pub fn get_duration(&self) -> Result<Duration, SynthizerError> {
-        let seconds = self.get_length_in_seconds()?;
-        let seconds = seconds as u64;
-        Ok(Duration::from_secs(seconds))
-    }
+        unsafe impl Sync for $object {}
+    };
 }
 
-unsafe impl Send for Buffer {}
-
-unsafe impl Sync for Buffer {}
-
-#[derive(Clone, Debug)]
-pub struct Context(Handle);
-
-impl Context {
-    fn new() -> Result<Self, SynthizerError> {
-        let mut handle = Handle(0);
-        wrap!(unsafe { syz_createContext(&mut *handle) }, Self(handle))
-    }
-
-    pub fn new_streaming_generator<S: Into<String>>(
-        &mut self,
-        protocol: Protocol,
-        path: &Path,
-        options: S,
-    ) -> Result<StreamingGenerator, SynthizerError> {
-        StreamingGenerator::new(&self, protocol, path, options)
-    }
-
-    pub fn new_buffer_generator(&mut self) -> Result<BufferGenerator, SynthizerError> {
-        BufferGenerator::new(&self)
-    }
-
-    pub fn new_noise_generator(&mut self, channels: u32) -> Result<NoiseGenerator, SynthizerError> {
-        NoiseGenerator::new(&self, channels)
-    }
-
-    pub fn new_direct_source(&mut self) -> Result<DirectSource, SynthizerError> {
-        DirectSource::new(&self)
-    }
-
-    pub fn new_panned_source(&mut self) -> Result<PannedSource, SynthizerError> {
-        PannedSource::new(&self)
-    }
+mod automation;
+mod buffer;
+mod context;
+mod doppler;
+mod effect;
+mod error;
+#[cfg(feature = "async")]
+mod event_stream;
+pub mod events;
+mod fade;
+mod generator;
+mod handle;
+#[cfg(feature = "mint")]
+mod math;
+mod occlusion;
+mod orientation;
+mod propagation;
+pub mod property;
+mod scaled;
+mod source;
+pub mod stream;
+mod wav;
+
+pub use automation::AutomationBatch;
+pub use buffer::*;
+pub use context::*;
+pub use doppler::DopplerConfig;
+pub use effect::*;
+pub use error::*;
+#[cfg(feature = "async")]
+pub use event_stream::{EventStream, Finished};
+pub use events::{Event, EventKind, EventReceiver, EventSource};
+pub use fade::Fade;
+pub use generator::*;
+pub use handle::*;
+#[cfg(feature = "mint")]
+pub use math::{Rotation, Vector};
+pub use occlusion::{Occluder, OcclusionConfig};
+pub use orientation::{Axis, CoordinateSystem, ListenerOrientation};
+pub use propagation::{Propagated, Propagation, PropagationConfig, PropagationGrid};
+pub use property::{Automatable, BiquadConfig, Property, PropertyValue};
+pub use source::*;
+pub use stream::StreamSource;
+pub use wav::WavFormat;
 
-    pub fn new_source3d(&mut self) -> Result<Source3D, SynthizerError> {
-        Source3D::new(&self)
-    }
-
-    fn handle(&self) -> &Handle {
-        &self.0
-    }
-
-    d3!(position, Property::Position);
-    d6!(orientation, Property::Orientation);
-    d!(distance_model, Property::DistanceModel);
-    d!(distance_ref, Property::DistanceRef);
-    d!(distance_max, Property::DistanceMax);
-    d!(rolloff, Property::Rolloff);
-    d!(closeness_boost, Property::ClosenessBoost);
-    d!(closeness_boost_distance, Property::ClosenessBoostDistance);
+pub enum LoggingBackend {
+    None = SYZ_LOGGING_BACKEND_SYZ_LOGGING_BACKEND_NONE as isize,
+    Stderr = SYZ_LOGGING_BACKEND_SYZ_LOGGING_BACKEND_STDERR as isize,
 }
 
-impl Deref for Context {
-    type Target = syz_Handle;
+static LOGGING_BACKEND: AtomicU32 =
+    AtomicU32::new(SYZ_LOGGING_BACKEND_SYZ_LOGGING_BACKEND_NONE as u32);
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
+// Until `set_log_level` is called, Synthizer follows `log::max_level()`.
+const LOG_LEVEL_FROM_LOG: u32 = u32::MAX;
 
-unsafe impl Send for Context {}
+static LOG_LEVEL: AtomicU32 = AtomicU32::new(LOG_LEVEL_FROM_LOG);
 
-unsafe impl Sync for Context {}
-
-macro_rules! make_subclass {
-    ($subclass:ident, $superclass:ty) => {
-        impl $superclass for $subclass {
-            fn handle(&self) -> &Handle {
-                &self.0
-            }
-        }
+/// Chooses where Synthizer writes its own log messages.
+///
+/// Synthizer reads its logging configuration at initialization, so this fails
+/// once Synthizer is initialized rather than silently doing nothing.
+///
+/// Native logs can't be forwarded to the `log` crate. Synthizer 0.11 only
+/// logs to stderr or nowhere, and offers no callback to receive its messages.
+pub fn configure_logging_backend(backend: LoggingBackend) -> Result<(), SynthizerError> {
+    let backend = match backend {
+        LoggingBackend::None => SYZ_LOGGING_BACKEND_SYZ_LOGGING_BACKEND_NONE,
+        LoggingBackend::Stderr => SYZ_LOGGING_BACKEND_SYZ_LOGGING_BACKEND_STDERR,
     };
+    // Holding the lock keeps Synthizer from initializing before this is
+    // stored.
+    let state = LIBRARY.lock().unwrap();
+    if state.initialized {
+        return Err(SynthizerError::InvalidArgument(
+            "Logging must be configured before Synthizer is initialized".to_string(),
+        ));
+    }
+    LOGGING_BACKEND.store(backend as u32, Ordering::SeqCst);
+    drop(state);
+    Ok(())
+}
+
+/// Like `configure_logging_backend`, this must be called before
+/// `Synthizer::new`. Synthizer's most verbose level is debug, so `Trace` logs
+/// the same as `Debug`.
+///
+/// If this is never called, the level is taken from `log::max_level()`.
+pub fn set_log_level(level: Level) {
+    LOG_LEVEL.store(log_level(level.to_level_filter()), Ordering::SeqCst);
 }
 
-pub trait Generator {
-    fn handle(&self) -> &Handle;
+fn log_level(level: LevelFilter) -> u32 {
+    let level = match level {
+        LevelFilter::Off | LevelFilter::Error => SYZ_LOG_LEVEL_SYZ_LOG_LEVEL_ERROR,
+        LevelFilter::Warn => SYZ_LOG_LEVEL_SYZ_LOG_LEVEL_WARN,
+        LevelFilter::Info => SYZ_LOG_LEVEL_SYZ_LOG_LEVEL_INFO,
+        LevelFilter::Debug | LevelFilter::Trace => SYZ_LOG_LEVEL_SYZ_LOG_LEVEL_DEBUG,
+    };
+    level as u32
 }
 
-#[derive(Clone, Debug)]
-pub struct StreamingGenerator(Handle);
-
-impl StreamingGenerator {
-    fn new<S: Into<String>>(
-        context: &Context,
-        protocol: Protocol,
-        path: &Path,
-        options: S,
-    ) -> Result<Self, SynthizerError> {
-        let mut handle = Handle(0);
-        let protocol = match protocol {
-            Protocol::File => String::from("file"),
-        };
-        let protocol = CString::new(protocol.as_bytes()).expect("Unable to create C string");
-        let protocol = protocol.as_ptr() as *const i8;
-        let path = path.as_os_str().to_string_lossy();
-        let path = CString::new(path.as_bytes()).expect("Unable to create C string");
-        let path = path.as_ptr() as *const i8;
-        let options = options.into();
-        let options = CString::new(options.as_bytes()).expect("Unable to create C string");
-        let options = options.as_ptr() as *const i8;
-        wrap!(
-            unsafe {
-                syz_createStreamingGenerator(&mut *handle, **context, protocol, path, options)
-            },
-            Self(handle)
-        )
-    }
+fn initialize() -> Result<(), SynthizerError> {
+    let config = syz_LibraryConfig {
+        log_level: match LOG_LEVEL.load(Ordering::SeqCst) {
+            LOG_LEVEL_FROM_LOG => log_level(log::max_level()),
+            v => v,
+        },
+        logging_backend: LOGGING_BACKEND.load(Ordering::SeqCst),
+        libsndfile_path: null(),
+    };
+    wrap!(unsafe { syz_initializeWithConfig(&config) })
 }
 
-make_subclass!(StreamingGenerator, Generator);
-
-unsafe impl Send for StreamingGenerator {}
-
-unsafe impl Sync for StreamingGenerator {}
-
-#[derive(Clone, Debug)]
-pub struct BufferGenerator(Handle);
-
-impl BufferGenerator {
-    fn new(context: &Context) -> Result<Self, SynthizerError> {
-        let mut handle = Handle(0);
-        wrap!(
-            unsafe { syz_createBufferGenerator(&mut *handle, **context) },
-            Self(handle)
-        )
-    }
-
-    pub fn get_buffer(&self) -> Result<Buffer, SynthizerError> {
-        let handle = self.handle().get_o(Property::Buffer.to_i32().unwrap())?;
-        Ok(Buffer(handle))
-    }
-
-    pub fn set_buffer(&self, buffer: Buffer) -> Result<(), SynthizerError> {
-        self.handle()
-            .set_o(Property::Buffer.to_i32().unwrap(), buffer.0)
-    }
+fn shutdown() -> Result<(), SynthizerError> {
+    wrap!(unsafe { syz_shutdown() })
+}
 
-    d!(position, Property::Position);
+/// Common functionality for every Synthizer object.
+pub trait Object {
+    fn handle(&self) -> &Handle;
 
-    pub fn get_looping(&self) -> Result<bool, SynthizerError> {
-        let v = self.handle().get_i(Property::Looping.to_i32().unwrap())?;
-        Ok(v == 1)
+    fn get<T: PropertyValue>(&self, property: Property<T>) -> Result<T, SynthizerError> {
+        self.handle().get(property)
     }
 
-    pub fn set_looping(&self, v: bool) -> Result<(), SynthizerError> {
-        let v = if v { 1 } else { 0 };
-        self.handle().set_i(Property::Looping.to_i32().unwrap(), v)
+    fn set<T: PropertyValue>(&self, property: Property<T>, value: T) -> Result<(), SynthizerError> {
+        self.handle().set(property, value)
     }
-
-    d!(pitch_bend, Property::PitchBend);
 }
 
-make_subclass!(BufferGenerator, Generator);
+/// Keeps Synthizer initialized for as long as it is referenced.
+///
+/// Every `Synthizer` guard and every handle holds one of these, so the library
+/// is only shut down once the last of them is dropped.
+#[derive(Debug)]
+pub(crate) struct Library;
 
-unsafe impl Send for BufferGenerator {}
-
-unsafe impl Sync for BufferGenerator {}
-
-#[derive(Clone, Debug)]
-pub struct NoiseGenerator(Handle);
-
-impl NoiseGenerator {
-    fn new(context: &Context, channels: u32) -> Result<Self, SynthizerError> {
-        let mut handle = Handle(0);
-        wrap!(
-            unsafe { syz_createNoiseGenerator(&mut *handle, **context, channels) },
-            Self(handle)
-        )
-    }
-
-    i!(noise_type, Property::NoiseType);
+/// Whether Synthizer is initialized, and the library keeping it so.
+struct LibraryState {
+    // A weak reference lets a second `Synthizer::new` share a live library.
+    library: Weak<Library>,
+    initialized: bool,
 }
 
-make_subclass!(NoiseGenerator, Generator);
-
-unsafe impl Send for NoiseGenerator {}
+static LIBRARY: Mutex<LibraryState> = Mutex::new(LibraryState {
+    library: Weak::new(),
+    initialized: false,
+});
 
-unsafe impl Sync for NoiseGenerator {}
-
-pub trait Source {
-    fn handle(&self) -> &Handle;
-
-    fn add_generator(&self, generator: &impl Generator) -> Result<(), SynthizerError> {
-        wrap!(unsafe { syz_sourceAddGenerator(**self.handle(), **generator.handle()) })
+impl LibraryState {
+    fn acquire(&mut self) -> Result<Arc<Library>, SynthizerError> {
+        if let Some(library) = self.library.upgrade() {
+            return Ok(library);
+        }
+        // The last library may have been dropped without getting the lock to
+        // shut down yet, in which case this one takes over from it.
+        if !self.initialized {
+            initialize()?;
+            self.initialized = true;
+        }
+        let library = Arc::new(Library);
+        self.library = Arc::downgrade(&library);
+        Ok(library)
     }
 
-    fn remove_generator(&self, generator: &impl Generator) -> Result<(), SynthizerError> {
-        wrap!(unsafe { syz_sourceRemoveGenerator(**self.handle(), **generator.handle()) })
+    /// Whether Synthizer should be shut down now that `library` is dropped.
+    fn release(&mut self, library: *const Library) -> bool {
+        if !std::ptr::eq(self.library.as_ptr(), library) {
+            return false;
+        }
+        self.library = Weak::new();
+        self.initialized = false;
+        true
     }
-
-    td!(gain, Property::Gain);
 }
 
-#[derive(Clone, Debug)]
-pub struct DirectSource(Handle);
-
-impl DirectSource {
-    fn new(context: &Context) -> Result<Self, SynthizerError> {
-        let mut handle = Handle(0);
-        wrap!(
-            unsafe { syz_createDirectSource(&mut *handle, **context) },
-            Self(handle)
-        )
+impl Library {
+    /// The library, for objects created outside any context. Fails unless a
+    /// guard or handle is keeping it initialized, so every object has one.
+    pub(crate) fn current() -> Result<Arc<Library>, SynthizerError> {
+        LIBRARY.lock().unwrap().library.upgrade().ok_or_else(|| {
+            SynthizerError::InvalidArgument("Synthizer isn't initialized".to_string())
+        })
     }
 }
 
-make_subclass!(DirectSource, Source);
-
-unsafe impl Send for DirectSource {}
-
-unsafe impl Sync for DirectSource {}
-
-trait SpatializedSource: Source {
-    ti!(panner_strategy, Property::PannerStrategy);
-}
-
-#[derive(Clone, Debug)]
-pub struct PannedSource(Handle);
-
-impl PannedSource {
-    fn new(context: &Context) -> Result<Self, SynthizerError> {
-        let mut handle = Handle(0);
-        wrap!(
-            unsafe { syz_createPannedSource(&mut *handle, **context) },
-            Self(handle)
-        )
+impl Drop for Library {
+    fn drop(&mut self) {
+        // Shut down with the lock held, so a concurrent `Synthizer::new`
+        // either takes over before this or initializes again after it.
+        let mut state = LIBRARY.lock().unwrap();
+        if state.release(self) {
+            if let Err(e) = shutdown() {
+                log::error!(target: "synthizer", "Failed to shut down: {}", e);
+            }
+        }
     }
-
-    d!(azimuth, Property::Azimuth);
-    d!(elevation, Property::Elevation);
-    d!(panning_scalar, Property::PanningScalar);
 }
 
-make_subclass!(PannedSource, Source);
-
-impl SpatializedSource for PannedSource {}
-
-unsafe impl Send for PannedSource {}
-
-unsafe impl Sync for PannedSource {}
-
+/// A guard for the Synthizer library.
+///
+/// Creating more than one `Synthizer` while another is alive shares the same
+/// initialized library. Synthizer is shut down once every guard, and every
+/// object created while it was initialized, has been dropped.
 #[derive(Clone, Debug)]
-pub struct Source3D(Handle);
-
-impl Source3D {
-    fn new(context: &Context) -> Result<Self, SynthizerError> {
-        let mut handle = Handle(0);
-        wrap!(
-            unsafe { syz_createSource3D(&mut *handle, **context) },
-            Self(handle)
-        )
-    }
-
-    d3!(position, Property::Position);
-    d6!(orientation, Property::Orientation);
-    d!(distance_model, Property::DistanceModel);
-    d!(distance_ref, Property::DistanceRef);
-    d!(distance_max, Property::DistanceMax);
-    d!(rolloff, Property::Rolloff);
-    d!(closeness_boost, Property::ClosenessBoost);
-    d!(closeness_boost_distance, Property::ClosenessBoostDistance);
+pub struct Synthizer {
+    library: Arc<Library>,
 }
 
-make_subclass!(Source3D, Source);
-
-impl SpatializedSource for Source3D {}
-
-unsafe impl Send for Source3D {}
-
-unsafe impl Sync for Source3D {}
-
-#[derive(Clone, Debug)]
-pub struct Synthizer;
-
 impl Synthizer {
     pub fn new() -> Result<Self, SynthizerError> {
-        initialize()?;
-        Ok(Synthizer)
+        let library = LIBRARY.lock().unwrap().acquire()?;
+        Ok(Synthizer { library })
     }
 
     pub fn new_context(&self) -> Result<Context, SynthizerError> {
-        Context::new()
+        Context::new(self.library.clone())
+    }
+
+    /// Creates a context which renders audio on demand instead of opening an
+    /// audio device.
+    pub fn new_headless_context(&self) -> Result<HeadlessContext, SynthizerError> {
+        HeadlessContext::new(self.library.clone())
+    }
+
+    /// Registers a stream protocol, for use with `Protocol::Custom`.
+    ///
+    /// `open` is called with the path of every stream opened with this
+    /// protocol, possibly from Synthizer's own threads. Protocols can't be
+    /// unregistered, and registering the same name twice fails, even across
+    /// reinitializations.
+    pub fn register_stream_protocol<F>(&self, protocol: &str, open: F) -> Result<(), SynthizerError>
+    where
+        F: Fn(&str) -> std::io::Result<Box<dyn StreamSource>> + Send + Sync + 'static,
+    {
+        stream::register_stream_protocol(protocol, open)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock, Source};
+
+    #[test]
+    fn log_levels_map_to_synthizer() {
+        assert_eq!(
+            log_level(LevelFilter::Off),
+            SYZ_LOG_LEVEL_SYZ_LOG_LEVEL_ERROR as u32
+        );
+        assert_eq!(
+            log_level(LevelFilter::Warn),
+            SYZ_LOG_LEVEL_SYZ_LOG_LEVEL_WARN as u32
+        );
+        assert_eq!(
+            log_level(LevelFilter::Trace),
+            SYZ_LOG_LEVEL_SYZ_LOG_LEVEL_DEBUG as u32
+        );
+    }
+
+    #[test]
+    fn logging_is_configured_before_initializing() -> Result<(), SynthizerError> {
+        let _synthizer = Synthizer::new()?;
+        let error = configure_logging_backend(LoggingBackend::None).unwrap_err();
+        assert!(matches!(error, SynthizerError::InvalidArgument(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn guards_share_one_library() -> Result<(), SynthizerError> {
+        let first = Synthizer::new()?;
+        let second = Synthizer::new()?;
+        assert!(Arc::ptr_eq(&first.library, &second.library));
+        Ok(())
+    }
+
+    #[test]
+    fn objects_keep_the_creating_library() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_direct_source()?;
+        let buffer = Buffer::from_float_samples(44100, 1, &[0.])?;
+        for handle in [context.handle(), source.handle(), buffer.handle()].iter() {
+            assert!(handle.library_is(&synthizer.library));
+        }
+        Ok(())
     }
-}
-
-unsafe impl Send for Synthizer {}
-
-unsafe impl Sync for Synthizer {}
 
-impl Drop for Synthizer {
-    fn drop(&mut self) {
-        shutdown().expect("Failed to shut down");
+    #[test]
+    fn replaced_library_does_not_shut_down() -> Result<(), SynthizerError> {
+        let mut state = LibraryState {
+            library: Weak::new(),
+            initialized: true,
+        };
+        // The last library is dropped, but hasn't got the lock to release
+        // itself before a new guard is made.
+        let old = Arc::new(Library);
+        state.library = Arc::downgrade(&old);
+        let old_ptr = Arc::as_ptr(&old);
+        drop(old);
+        let new = state.acquire()?;
+        assert!(!state.release(old_ptr));
+        assert!(state.initialized);
+        let new_ptr = Arc::as_ptr(&new);
+        drop(new);
+        assert!(state.release(new_ptr));
+        assert!(!state.initialized);
+        Ok(())
+    }
+
+    #[test]
+    fn objects_keep_library_alive() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        let generator = context.new_buffer_generator()?;
+        source.add_generator(&generator)?;
+        let handles = [**context.handle(), **source.handle(), **generator.handle()];
+        drop(synthizer);
+        assert!(mock::is_initialized());
+        source.set_gain(0.5)?;
+        drop(context);
+        drop(generator);
+        drop(source);
+        for handle in handles.iter() {
+            assert_eq!(mock::released_after_shutdown(*handle), 0);
+            assert_eq!(mock::refcount(*handle), None);
+        }
+        Ok(())
     }
 }
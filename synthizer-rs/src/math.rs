@@ -0,0 +1,278 @@
+//! Positions and orientations as math library types.
+//!
+//! With the `mint` feature, anything convertible to and from
+//! `mint::Vector3` or `mint::Quaternion` can be used for positions and
+//! orientations. The `glam` and `nalgebra` features add their types too.
+//!
+//! Vectors are in the context's
+//! [`CoordinateSystem`](crate::CoordinateSystem), and quaternions rotate its
+//! default facing, forward with up up, to get the at/up pair Synthizer stores
+//! as an orientation.
+
+use crate::{Context, Object, Source3D, SynthizerError};
+
+/// A 3D vector usable as a position.
+pub trait Vector: Copy {
+    fn to_xyz(self) -> (f64, f64, f64);
+
+    fn from_xyz(x: f64, y: f64, z: f64) -> Self;
+}
+
+/// A rotation usable as an orientation.
+pub trait Rotation: Copy {
+    fn to_wxyz(self) -> [f64; 4];
+
+    fn from_wxyz(q: [f64; 4]) -> Self;
+}
+
+macro_rules! vector {
+    ($($type:ty: $mint:ident<$scalar:ty>),*) => {
+        $(
+            impl Vector for $type {
+                fn to_xyz(self) -> (f64, f64, f64) {
+                    let v: mint::$mint<$scalar> = self.into();
+                    (v.x as f64, v.y as f64, v.z as f64)
+                }
+
+                fn from_xyz(x: f64, y: f64, z: f64) -> Self {
+                    mint::$mint {
+                        x: x as $scalar,
+                        y: y as $scalar,
+                        z: z as $scalar,
+                    }
+                    .into()
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! rotation {
+    ($($type:ty: $scalar:ty),*) => {
+        $(
+            impl Rotation for $type {
+                fn to_wxyz(self) -> [f64; 4] {
+                    let q: mint::Quaternion<$scalar> = self.into();
+                    [q.s as f64, q.v.x as f64, q.v.y as f64, q.v.z as f64]
+                }
+
+                fn from_wxyz([w, x, y, z]: [f64; 4]) -> Self {
+                    mint::Quaternion {
+                        s: w as $scalar,
+                        v: mint::Vector3 {
+                            x: x as $scalar,
+                            y: y as $scalar,
+                            z: z as $scalar,
+                        },
+                    }
+                    .into()
+                }
+            }
+        )*
+    };
+}
+
+vector!(
+    mint::Vector3<f32>: Vector3<f32>,
+    mint::Vector3<f64>: Vector3<f64>,
+    mint::Point3<f32>: Point3<f32>,
+    mint::Point3<f64>: Point3<f64>
+);
+rotation!(mint::Quaternion<f32>: f32, mint::Quaternion<f64>: f64);
+
+#[cfg(feature = "glam")]
+vector!(
+    glam::Vec3: Vector3<f32>,
+    glam::Vec3A: Vector3<f32>,
+    glam::DVec3: Vector3<f64>
+);
+#[cfg(feature = "glam")]
+rotation!(glam::Quat: f32, glam::DQuat: f64);
+
+#[cfg(feature = "nalgebra")]
+vector!(
+    nalgebra::Vector3<f32>: Vector3<f32>,
+    nalgebra::Vector3<f64>: Vector3<f64>,
+    nalgebra::Point3<f32>: Point3<f32>,
+    nalgebra::Point3<f64>: Point3<f64>
+);
+
+// nalgebra only converts mint quaternions into plain quaternions, so unit
+// quaternions are normalized here.
+#[cfg(feature = "nalgebra")]
+macro_rules! unit_quaternion {
+    ($($scalar:ty),*) => {
+        $(
+            impl Rotation for nalgebra::UnitQuaternion<$scalar> {
+                fn to_wxyz(self) -> [f64; 4] {
+                    let q: mint::Quaternion<$scalar> = self.into();
+                    q.to_wxyz()
+                }
+
+                fn from_wxyz(q: [f64; 4]) -> Self {
+                    let q = mint::Quaternion::<$scalar>::from_wxyz(q);
+                    nalgebra::UnitQuaternion::new_normalize(q.into())
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "nalgebra")]
+unit_quaternion!(f32, f64);
+
+macro_rules! accessors {
+    ($($object:ty),*) => {
+        $(
+            impl $object {
+                /// Sets the position from any supported vector type.
+                pub fn set_position_vec(&self, position: impl Vector) -> Result<(), SynthizerError> {
+                    let (x, y, z) = position.to_xyz();
+                    self.set_position(x, y, z)
+                }
+
+                /// Gets the position as any supported vector type.
+                pub fn get_position_vec<T: Vector>(&self) -> Result<T, SynthizerError> {
+                    let (x, y, z) = self.get_position()?;
+                    Ok(T::from_xyz(x, y, z))
+                }
+
+                /// Sets the orientation by rotating the default facing.
+                pub fn set_orientation_quat(
+                    &self,
+                    orientation: impl Rotation,
+                ) -> Result<(), SynthizerError> {
+                    let system = self.handle().coordinate_system();
+                    let (at, up) = system.rotate(orientation.to_wxyz())?;
+                    self.set_orientation(at[0], at[1], at[2], up[0], up[1], up[2])
+                }
+
+                /// Gets the orientation as the rotation from the default
+                /// facing.
+                pub fn get_orientation_quat<T: Rotation>(&self) -> Result<T, SynthizerError> {
+                    let (x1, y1, z1, x2, y2, z2) = self.get_orientation()?;
+                    let system = self.handle().coordinate_system();
+                    Ok(T::from_wxyz(system.rotation([x1, y1, z1], [x2, y2, z2])?))
+                }
+            }
+        )*
+    };
+}
+
+accessors!(Context, Source3D);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Synthizer;
+
+    fn quaternion(s: f64, x: f64, y: f64, z: f64) -> mint::Quaternion<f64> {
+        mint::Quaternion {
+            s,
+            v: mint::Vector3 { x, y, z },
+        }
+    }
+
+    fn close(a: (f64, f64, f64, f64, f64, f64), b: (f64, f64, f64, f64, f64, f64)) -> bool {
+        let (a, b) = (
+            [a.0, a.1, a.2, a.3, a.4, a.5],
+            [b.0, b.1, b.2, b.3, b.4, b.5],
+        );
+        (0..6).all(|i| (a[i] - b[i]).abs() < 1e-9)
+    }
+
+    #[test]
+    fn rotates_default_facing() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let context = synthizer.new_context()?;
+        context.set_orientation_quat(quaternion(1., 0., 0., 0.))?;
+        assert_eq!(context.get_orientation()?, (0., 1., 0., 0., 0., 1.));
+        // A quarter turn left, about +z.
+        let half = std::f64::consts::FRAC_PI_4;
+        let left = quaternion(half.cos(), 0., 0., half.sin());
+        context.set_orientation_quat(left)?;
+        assert!(close(context.get_orientation()?, (-1., 0., 0., 0., 0., 1.)));
+        let back: mint::Quaternion<f64> = context.get_orientation_quat()?;
+        assert!((back.s - left.s).abs() < 1e-9 && (back.v.z - left.v.z).abs() < 1e-9);
+        assert!(context
+            .set_orientation_quat(quaternion(0., 0., 0., 0.))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn follows_the_coordinate_system() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let context = synthizer.new_context()?;
+        context.set_coordinate_system(crate::CoordinateSystem::Y_UP_RIGHT_HANDED);
+        context.set_orientation_quat(quaternion(1., 0., 0., 0.))?;
+        // -z forward and +y up, which are Synthizer's +y and +z.
+        assert_eq!(context.get_orientation()?, (0., 0., -1., 0., 1., 0.));
+        let identity: mint::Quaternion<f64> = context.get_orientation_quat()?;
+        assert!((identity.s.abs() - 1.).abs() < 1e-9);
+        context.set_position_vec(mint::Vector3 {
+            x: 1.,
+            y: 2.,
+            z: 3.,
+        })?;
+        let (mut x, mut y, mut z) = (0., 0., 0.);
+        let position = crate::property::POSITION.id();
+        unsafe { crate::ffi::syz_getD3(&mut x, &mut y, &mut z, **context.handle(), position) };
+        assert_eq!((x, y, z), (1., -3., 2.));
+        Ok(())
+    }
+
+    #[test]
+    fn sets_positions_and_orientations() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        source.set_position_vec(mint::Vector3 {
+            x: 1f32,
+            y: 2.,
+            z: 3.,
+        })?;
+        assert_eq!(source.get_position()?, (1., 2., 3.));
+        let position: mint::Vector3<f64> = source.get_position_vec()?;
+        assert_eq!((position.x, position.y, position.z), (1., 2., 3.));
+        context.set_orientation(1., 0., 0., 0., 0., 1.)?;
+        let q: mint::Quaternion<f64> = context.get_orientation_quat()?;
+        context.set_orientation_quat(q)?;
+        assert!(close(context.get_orientation()?, (1., 0., 0., 0., 0., 1.)));
+        Ok(())
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn accepts_glam() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let context = synthizer.new_context()?;
+        context.set_position_vec(glam::Vec3::new(1., 2., 3.))?;
+        assert_eq!(
+            context.get_position_vec::<glam::DVec3>()?,
+            glam::DVec3::new(1., 2., 3.)
+        );
+        context.set_orientation_quat(glam::Quat::from_rotation_z(std::f32::consts::PI))?;
+        let (x1, y1, _, _, _, z2) = context.get_orientation()?;
+        assert!((x1.abs() < 1e-6) && (y1 + 1.).abs() < 1e-6 && (z2 - 1.).abs() < 1e-6);
+        let q = context.get_orientation_quat::<glam::DQuat>()?;
+        assert!(q.abs_diff_eq(glam::DQuat::from_rotation_z(std::f64::consts::PI), 1e-6));
+        Ok(())
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn accepts_nalgebra() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        source.set_position_vec(nalgebra::Point3::new(4., 5., 6.))?;
+        let position: nalgebra::Vector3<f32> = source.get_position_vec()?;
+        assert_eq!(position, nalgebra::Vector3::new(4., 5., 6.));
+        let rotation = nalgebra::UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3);
+        source.set_orientation_quat(rotation)?;
+        let back: nalgebra::UnitQuaternion<f64> = source.get_orientation_quat()?;
+        assert!(back.angle_to(&rotation) < 1e-6);
+        Ok(())
+    }
+}
@@ -0,0 +1,1144 @@
+//! An in-memory stand-in for the Synthizer C API, used by the test suite.
+//!
+//! Only the functions this crate calls are mocked. Each shadows the
+//! `synthizer_sys` function of the same name, so the rest of the crate is
+//! compiled against it unchanged.
+
+#![allow(non_snake_case)]
+#![allow(clippy::missing_safety_doc)]
+#![allow(clippy::too_many_arguments)]
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, VecDeque},
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int, c_uint, c_ulonglong, c_void},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+pub use synthizer_sys::*;
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+// Like Synthizer, every failure shares one code and is told apart by its
+// message.
+fn fail(message: &str) -> syz_ErrorCode {
+    LAST_ERROR.with(|v| *v.borrow_mut() = CString::new(message).unwrap());
+    1
+}
+
+fn invalid_handle() -> syz_ErrorCode {
+    fail("C handle is invalid")
+}
+
+fn property_type_mismatch() -> syz_ErrorCode {
+    fail("Property type mismatch")
+}
+
+fn not_initialized() -> syz_ErrorCode {
+    fail("The library is not initialized.")
+}
+
+#[derive(Clone, Copy)]
+enum Value {
+    I(c_int),
+    D(f64),
+    D3([f64; 3]),
+    D6([f64; 6]),
+    Biquad(syz_BiquadConfig),
+}
+
+struct Object {
+    object_type: c_int,
+    paused: bool,
+    refcount: usize,
+    properties: HashMap<c_int, Value>,
+}
+
+static OBJECTS: Mutex<BTreeMap<syz_Handle, Object>> = Mutex::new(BTreeMap::new());
+
+// Decrements against handles which were already freed.
+static DOUBLE_FREES: Mutex<BTreeMap<syz_Handle, usize>> = Mutex::new(BTreeMap::new());
+
+// Handles released after Synthizer was shut down.
+static AFTER_SHUTDOWN: Mutex<BTreeMap<syz_Handle, usize>> = Mutex::new(BTreeMap::new());
+
+// Synthizer's initialization nests.
+static INITIALIZED: AtomicUsize = AtomicUsize::new(0);
+
+// Samples rendered by each headless context.
+static RENDERED: Mutex<BTreeMap<syz_Handle, usize>> = Mutex::new(BTreeMap::new());
+
+// Routes from outputs to inputs.
+static ROUTES: Mutex<BTreeMap<(syz_Handle, syz_Handle), syz_RouteConfig>> =
+    Mutex::new(BTreeMap::new());
+
+// Commands added to each automation batch, or `None` once executed.
+static BATCHES: Mutex<BTreeMap<syz_Handle, Option<Vec<syz_AutomationCommand>>>> =
+    Mutex::new(BTreeMap::new());
+
+// Points executed on each object's properties.
+#[allow(clippy::type_complexity)]
+static AUTOMATION: Mutex<BTreeMap<(syz_Handle, c_int), Vec<(f64, c_int, [f64; 6])>>> =
+    Mutex::new(BTreeMap::new());
+
+// User events scheduled on each object.
+static USER_EVENTS: Mutex<BTreeMap<syz_Handle, Vec<(f64, u64)>>> = Mutex::new(BTreeMap::new());
+
+// The waves of each sine bank generator.
+static SINE_BANKS: Mutex<BTreeMap<syz_Handle, Vec<syz_SineBankWave>>> = Mutex::new(BTreeMap::new());
+
+// The taps of each echo.
+static ECHO_TAPS: Mutex<BTreeMap<syz_Handle, Vec<syz_EchoTapConfig>>> = Mutex::new(BTreeMap::new());
+
+// The sample rate, channel count and samples of buffers created from
+// arrays.
+#[allow(clippy::type_complexity)]
+static ARRAYS: Mutex<BTreeMap<syz_Handle, (c_uint, c_uint, Vec<f32>)>> =
+    Mutex::new(BTreeMap::new());
+
+// Events queued on each context.
+static EVENTS: Mutex<BTreeMap<syz_Handle, VecDeque<syz_Event>>> = Mutex::new(BTreeMap::new());
+
+// Stream protocols, with their userdata as an integer so it can be shared.
+static PROTOCOLS: Mutex<BTreeMap<String, (syz_StreamOpenCallback, usize)>> =
+    Mutex::new(BTreeMap::new());
+
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+fn create(out: *mut syz_Handle, object_type: SYZ_OBJECT_TYPE) -> syz_ErrorCode {
+    if INITIALIZED.load(Ordering::SeqCst) == 0 {
+        return not_initialized();
+    }
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::SeqCst);
+    let object = Object {
+        object_type: object_type as c_int,
+        paused: false,
+        refcount: 1,
+        properties: Default::default(),
+    };
+    OBJECTS.lock().unwrap().insert(handle, object);
+    unsafe { *out = handle };
+    0
+}
+
+fn set(target: syz_Handle, property: c_int, value: Value) -> syz_ErrorCode {
+    let mut objects = OBJECTS.lock().unwrap();
+    if let Some(object) = objects.get_mut(&target) {
+        object.properties.insert(property, value);
+        0
+    } else {
+        invalid_handle()
+    }
+}
+
+fn get(target: syz_Handle, property: c_int) -> Result<Option<Value>, syz_ErrorCode> {
+    let objects = OBJECTS.lock().unwrap();
+    let object = objects.get(&target).ok_or_else(invalid_handle)?;
+    Ok(object.properties.get(&property).cloned())
+}
+
+/// The number of live references to `handle`, or `None` if it was freed.
+pub(crate) fn refcount(handle: syz_Handle) -> Option<usize> {
+    OBJECTS.lock().unwrap().get(&handle).map(|v| v.refcount)
+}
+
+pub(crate) fn double_frees(handle: syz_Handle) -> usize {
+    DOUBLE_FREES
+        .lock()
+        .unwrap()
+        .get(&handle)
+        .cloned()
+        .unwrap_or(0)
+}
+
+pub(crate) fn released_after_shutdown(handle: syz_Handle) -> usize {
+    AFTER_SHUTDOWN
+        .lock()
+        .unwrap()
+        .get(&handle)
+        .cloned()
+        .unwrap_or(0)
+}
+
+/// The sample rate, channel count and interleaved samples `buffer` was
+/// created from, if it came from an array.
+pub(crate) fn buffer_samples(buffer: syz_Handle) -> Option<(u32, u32, Vec<f32>)> {
+    ARRAYS.lock().unwrap().get(&buffer).cloned()
+}
+
+pub(crate) fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::SeqCst) > 0
+}
+
+pub unsafe fn syz_getLastErrorMessage() -> *const c_char {
+    LAST_ERROR.with(|v| v.borrow().as_ptr())
+}
+
+pub unsafe fn syz_initializeWithConfig(_config: *const syz_LibraryConfig) -> syz_ErrorCode {
+    INITIALIZED.fetch_add(1, Ordering::SeqCst);
+    0
+}
+
+pub unsafe fn syz_shutdown() -> syz_ErrorCode {
+    let result = INITIALIZED.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| v.checked_sub(1));
+    if result.is_ok() {
+        0
+    } else {
+        not_initialized()
+    }
+}
+
+pub unsafe fn syz_handleIncRef(handle: syz_Handle) -> syz_ErrorCode {
+    if let Some(object) = OBJECTS.lock().unwrap().get_mut(&handle) {
+        object.refcount += 1;
+        0
+    } else {
+        invalid_handle()
+    }
+}
+
+pub unsafe fn syz_handleDecRef(handle: syz_Handle) -> syz_ErrorCode {
+    if !is_initialized() {
+        *AFTER_SHUTDOWN.lock().unwrap().entry(handle).or_default() += 1;
+        return not_initialized();
+    }
+    let mut objects = OBJECTS.lock().unwrap();
+    if let Some(object) = objects.get_mut(&handle) {
+        object.refcount -= 1;
+        if object.refcount == 0 {
+            objects.remove(&handle);
+        }
+        0
+    } else {
+        *DOUBLE_FREES.lock().unwrap().entry(handle).or_default() += 1;
+        invalid_handle()
+    }
+}
+
+pub(crate) fn is_paused(handle: syz_Handle) -> bool {
+    OBJECTS
+        .lock()
+        .unwrap()
+        .get(&handle)
+        .map(|v| v.paused)
+        .unwrap_or(false)
+}
+
+fn set_paused(handle: syz_Handle, paused: bool) -> syz_ErrorCode {
+    match OBJECTS.lock().unwrap().get_mut(&handle) {
+        Some(object) => {
+            object.paused = paused;
+            0
+        }
+        None => invalid_handle(),
+    }
+}
+
+pub unsafe fn syz_pause(object: syz_Handle) -> syz_ErrorCode {
+    set_paused(object, true)
+}
+
+pub unsafe fn syz_play(object: syz_Handle) -> syz_ErrorCode {
+    set_paused(object, false)
+}
+
+pub unsafe fn syz_getI(out: *mut c_int, target: syz_Handle, property: c_int) -> syz_ErrorCode {
+    match get(target, property) {
+        Ok(Some(Value::I(v))) => *out = v,
+        Ok(None) => *out = 0,
+        Ok(_) => return property_type_mismatch(),
+        Err(e) => return e,
+    }
+    0
+}
+
+pub unsafe fn syz_setI(target: syz_Handle, property: c_int, value: c_int) -> syz_ErrorCode {
+    set(target, property, Value::I(value))
+}
+
+pub unsafe fn syz_getD(out: *mut f64, target: syz_Handle, property: c_int) -> syz_ErrorCode {
+    match get(target, property) {
+        Ok(Some(Value::D(v))) => *out = v,
+        Ok(None) => *out = 0.,
+        Ok(_) => return property_type_mismatch(),
+        Err(e) => return e,
+    }
+    0
+}
+
+pub unsafe fn syz_setD(target: syz_Handle, property: c_int, value: f64) -> syz_ErrorCode {
+    set(target, property, Value::D(value))
+}
+
+pub unsafe fn syz_setO(target: syz_Handle, _property: c_int, value: syz_Handle) -> syz_ErrorCode {
+    if value != 0 && get(value, -1).is_err() {
+        return invalid_handle();
+    }
+    match get(target, -1) {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+pub unsafe fn syz_getD3(
+    x: *mut f64,
+    y: *mut f64,
+    z: *mut f64,
+    target: syz_Handle,
+    property: c_int,
+) -> syz_ErrorCode {
+    let v = match get(target, property) {
+        Ok(Some(Value::D3(v))) => v,
+        Ok(None) => [0.; 3],
+        Ok(_) => return property_type_mismatch(),
+        Err(e) => return e,
+    };
+    *x = v[0];
+    *y = v[1];
+    *z = v[2];
+    0
+}
+
+pub unsafe fn syz_setD3(
+    target: syz_Handle,
+    property: c_int,
+    x: f64,
+    y: f64,
+    z: f64,
+) -> syz_ErrorCode {
+    set(target, property, Value::D3([x, y, z]))
+}
+
+pub unsafe fn syz_getD6(
+    x1: *mut f64,
+    y1: *mut f64,
+    z1: *mut f64,
+    x2: *mut f64,
+    y2: *mut f64,
+    z2: *mut f64,
+    target: syz_Handle,
+    property: c_int,
+) -> syz_ErrorCode {
+    let v = match get(target, property) {
+        Ok(Some(Value::D6(v))) => v,
+        Ok(None) => [0.; 6],
+        Ok(_) => return property_type_mismatch(),
+        Err(e) => return e,
+    };
+    *x1 = v[0];
+    *y1 = v[1];
+    *z1 = v[2];
+    *x2 = v[3];
+    *y2 = v[4];
+    *z2 = v[5];
+    0
+}
+
+pub unsafe fn syz_setD6(
+    handle: syz_Handle,
+    property: c_int,
+    x1: f64,
+    y1: f64,
+    z1: f64,
+    x2: f64,
+    y2: f64,
+    z2: f64,
+) -> syz_ErrorCode {
+    set(handle, property, Value::D6([x1, y1, z1, x2, y2, z2]))
+}
+
+pub unsafe fn syz_biquadDesignIdentity(filter: *mut syz_BiquadConfig) -> syz_ErrorCode {
+    *filter = std::mem::zeroed();
+    (*filter)._b0 = 1.;
+    (*filter)._gain = 1.;
+    (*filter)._is_wire = 1;
+    0
+}
+
+// The Audio EQ Cookbook designs Synthizer uses, normalized so a0 is 1.
+unsafe fn design(
+    filter: *mut syz_BiquadConfig,
+    frequency: f64,
+    alpha: impl Fn(f64) -> f64,
+    b: impl Fn(f64) -> [f64; 3],
+) -> syz_ErrorCode {
+    let omega = 2. * std::f64::consts::PI * frequency / crate::SAMPLE_RATE as f64;
+    let alpha = alpha(omega);
+    let [b0, b1, b2] = b(omega.cos());
+    let a0 = 1. + alpha;
+    *filter = std::mem::zeroed();
+    let filter = &mut *filter;
+    filter._b0 = b0 / a0;
+    filter._b1 = b1 / a0;
+    filter._b2 = b2 / a0;
+    filter._a1 = -2. * omega.cos() / a0;
+    filter._a2 = (1. - alpha) / a0;
+    filter._gain = 1.;
+    0
+}
+
+pub unsafe fn syz_biquadDesignLowpass(
+    filter: *mut syz_BiquadConfig,
+    frequency: f64,
+    q: f64,
+) -> syz_ErrorCode {
+    design(
+        filter,
+        frequency,
+        |w| w.sin() / (2. * q),
+        |cos| [(1. - cos) / 2., 1. - cos, (1. - cos) / 2.],
+    )
+}
+
+pub unsafe fn syz_biquadDesignHighpass(
+    filter: *mut syz_BiquadConfig,
+    frequency: f64,
+    q: f64,
+) -> syz_ErrorCode {
+    design(
+        filter,
+        frequency,
+        |w| w.sin() / (2. * q),
+        |cos| [(1. + cos) / 2., -(1. + cos), (1. + cos) / 2.],
+    )
+}
+
+pub unsafe fn syz_biquadDesignBandpass(
+    filter: *mut syz_BiquadConfig,
+    frequency: f64,
+    bw: f64,
+) -> syz_ErrorCode {
+    let alpha = move |w: f64| w.sin() * (2f64.ln() / 2. * bw * w / w.sin()).sinh();
+    design(filter, frequency, alpha, move |cos| {
+        let w = cos.acos();
+        let alpha = alpha(w);
+        [alpha, 0., -alpha]
+    })
+}
+
+pub unsafe fn syz_getBiquad(
+    filter: *mut syz_BiquadConfig,
+    target: syz_Handle,
+    property: c_int,
+) -> syz_ErrorCode {
+    match get(target, property) {
+        Ok(Some(Value::Biquad(v))) => *filter = v,
+        Ok(None) => {}
+        Ok(_) => return property_type_mismatch(),
+        Err(e) => return e,
+    }
+    0
+}
+
+pub unsafe fn syz_setBiquad(
+    target: syz_Handle,
+    property: c_int,
+    filter: *const syz_BiquadConfig,
+) -> syz_ErrorCode {
+    set(target, property, Value::Biquad(*filter))
+}
+
+pub unsafe fn syz_createContext(
+    out: *mut syz_Handle,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_CONTEXT)
+}
+
+pub unsafe fn syz_createContextHeadless(
+    out: *mut syz_Handle,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_CONTEXT)
+}
+
+/// Fills the block with the index of each sample rendered so far, so tests
+/// can check nothing was lost or repeated.
+pub unsafe fn syz_contextGetBlock(context: syz_Handle, block: *mut f32) -> syz_ErrorCode {
+    if let Err(e) = get(context, -1) {
+        return e;
+    }
+    let mut rendered = RENDERED.lock().unwrap();
+    let start = rendered.entry(context).or_default();
+    for i in 0..512 {
+        *block.add(i) = (*start + i) as f32;
+    }
+    *start += 512;
+    let time = (*start / 2) as f64 / crate::SAMPLE_RATE as f64;
+    set(
+        context,
+        SYZ_PROPERTIES_SYZ_P_CURRENT_TIME as c_int,
+        Value::D(time),
+    );
+    let suggested = SYZ_PROPERTIES_SYZ_P_SUGGESTED_AUTOMATION_TIME as c_int;
+    set(context, suggested, Value::D(time + 0.05))
+}
+
+pub unsafe fn syz_createAutomationBatch(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    let result = create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_AUTOMATION_BATCH);
+    if result == 0 {
+        BATCHES.lock().unwrap().insert(*out, Some(vec![]));
+    }
+    result
+}
+
+fn batch_consumed() -> syz_ErrorCode {
+    fail("AutomationBatch cannot be reused after execution")
+}
+
+pub unsafe fn syz_automationBatchAddCommands(
+    batch: syz_Handle,
+    commands_len: c_ulonglong,
+    commands: *const syz_AutomationCommand,
+) -> syz_ErrorCode {
+    let commands = std::slice::from_raw_parts(commands, commands_len as usize);
+    if commands.iter().any(|v| get(v.target, -1).is_err()) {
+        return invalid_handle();
+    }
+    match BATCHES.lock().unwrap().get_mut(&batch) {
+        Some(Some(batch)) => batch.extend_from_slice(commands),
+        Some(None) => return batch_consumed(),
+        None => return invalid_handle(),
+    }
+    0
+}
+
+/// The automation points waiting on a property, as time, interpolation and
+/// values.
+pub(crate) fn automation(target: syz_Handle, property: c_int) -> Vec<(f64, c_int, [f64; 6])> {
+    AUTOMATION
+        .lock()
+        .unwrap()
+        .get(&(target, property))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// The user events scheduled on an object, as time and param.
+pub(crate) fn user_events(target: syz_Handle) -> Vec<(f64, u64)> {
+    USER_EVENTS
+        .lock()
+        .unwrap()
+        .get(&target)
+        .cloned()
+        .unwrap_or_default()
+}
+
+pub unsafe fn syz_automationBatchExecute(batch: syz_Handle) -> syz_ErrorCode {
+    let commands = match BATCHES.lock().unwrap().get_mut(&batch) {
+        Some(commands) => match commands.take() {
+            Some(commands) => commands,
+            None => return batch_consumed(),
+        },
+        None => return invalid_handle(),
+    };
+    let mut automation = AUTOMATION.lock().unwrap();
+    for command in commands {
+        match command.type_ as u32 {
+            v if v == SYZ_AUTOMATION_COMMANDS_SYZ_AUTOMATION_COMMAND_APPEND_PROPERTY as u32 => {
+                let params = command.params.append_to_property;
+                let point = (
+                    command.time,
+                    params.point.interpolation_type,
+                    params.point.values,
+                );
+                automation
+                    .entry((command.target, params.property))
+                    .or_default()
+                    .push(point);
+            }
+            v if v == SYZ_AUTOMATION_COMMANDS_SYZ_AUTOMATION_COMMAND_CLEAR_PROPERTY as u32 => {
+                let property = command.params.clear_property.property;
+                automation.remove(&(command.target, property));
+            }
+            v if v
+                == SYZ_AUTOMATION_COMMANDS_SYZ_AUTOMATION_COMMAND_CLEAR_ALL_PROPERTIES as u32 =>
+            {
+                automation.retain(|k, _| k.0 != command.target);
+            }
+            v if v == SYZ_AUTOMATION_COMMANDS_SYZ_AUTOMATION_COMMAND_SEND_USER_EVENT as u32 => {
+                let param = command.params.send_user_event.param;
+                USER_EVENTS
+                    .lock()
+                    .unwrap()
+                    .entry(command.target)
+                    .or_default()
+                    .push((command.time, param));
+            }
+            v if v == SYZ_AUTOMATION_COMMANDS_SYZ_AUTOMATION_COMMAND_CLEAR_EVENTS as u32 => {
+                USER_EVENTS.lock().unwrap().remove(&command.target);
+            }
+            _ => {}
+        }
+    }
+    0
+}
+
+pub unsafe fn syz_createGlobalEcho(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_GLOBAL_ECHO)
+}
+
+pub unsafe fn syz_createGlobalFdnReverb(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_GLOBAL_FDN_REVERB)
+}
+
+/// The taps last given to an echo.
+pub(crate) fn echo_taps(echo: syz_Handle) -> Vec<crate::EchoTap> {
+    ECHO_TAPS
+        .lock()
+        .unwrap()
+        .get(&echo)
+        .map(|v| {
+            v.iter()
+                .map(|v| crate::EchoTap::new(v.delay, v.gain_l, v.gain_r))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub unsafe fn syz_globalEchoSetTaps(
+    handle: syz_Handle,
+    n_taps: c_uint,
+    taps: *const syz_EchoTapConfig,
+) -> syz_ErrorCode {
+    if let Err(e) = get(handle, -1) {
+        return e;
+    }
+    let taps = std::slice::from_raw_parts(taps, n_taps as usize);
+    if taps.iter().any(|v| v.delay > crate::MAX_ECHO_DELAY) {
+        return fail("Delay is too long");
+    }
+    ECHO_TAPS.lock().unwrap().insert(handle, taps.to_vec());
+    0
+}
+
+pub unsafe fn syz_effectReset(effect: syz_Handle) -> syz_ErrorCode {
+    match get(effect, -1) {
+        Ok(_) => 0,
+        Err(e) => e,
+    }
+}
+
+pub unsafe fn syz_initRouteConfig(config: *mut syz_RouteConfig) -> syz_ErrorCode {
+    *config = std::mem::zeroed();
+    (*config).gain = 1.;
+    (*config).fade_time = 0.03;
+    0
+}
+
+/// The gain and fade time of the route from `output` to `input`.
+pub(crate) fn route(output: syz_Handle, input: syz_Handle) -> Option<(f64, f64)> {
+    ROUTES
+        .lock()
+        .unwrap()
+        .get(&(output, input))
+        .map(|v| (v.gain, v.fade_time))
+}
+
+pub unsafe fn syz_routingConfigRoute(
+    _context: syz_Handle,
+    output: syz_Handle,
+    input: syz_Handle,
+    config: *const syz_RouteConfig,
+) -> syz_ErrorCode {
+    if get(output, -1).is_err() || get(input, -1).is_err() {
+        return invalid_handle();
+    }
+    ROUTES.lock().unwrap().insert((output, input), *config);
+    0
+}
+
+pub unsafe fn syz_routingRemoveRoute(
+    _context: syz_Handle,
+    output: syz_Handle,
+    input: syz_Handle,
+    _fade_out: f64,
+) -> syz_ErrorCode {
+    ROUTES.lock().unwrap().remove(&(output, input));
+    0
+}
+
+pub unsafe fn syz_routingRemoveAllRoutes(
+    _context: syz_Handle,
+    output: syz_Handle,
+    _fade_out: f64,
+) -> syz_ErrorCode {
+    ROUTES.lock().unwrap().retain(|k, _| k.0 != output);
+    0
+}
+
+pub unsafe fn syz_handleGetObjectType(out: *mut c_int, handle: syz_Handle) -> syz_ErrorCode {
+    match OBJECTS.lock().unwrap().get(&handle) {
+        Some(object) => {
+            *out = object.object_type;
+            0
+        }
+        None => invalid_handle(),
+    }
+}
+
+pub unsafe fn syz_contextEnableEvents(context: syz_Handle) -> syz_ErrorCode {
+    if let Err(e) = get(context, -1) {
+        return e;
+    }
+    EVENTS.lock().unwrap().entry(context).or_default();
+    0
+}
+
+/// Queues an event on a context with events enabled, referencing its source
+/// and context as Synthizer does.
+pub(crate) fn push_event(
+    context: syz_Handle,
+    event_type: SYZ_EVENT_TYPES,
+    source: syz_Handle,
+    param: u64,
+) {
+    let mut events = EVENTS.lock().unwrap();
+    if let Some(events) = events.get_mut(&context) {
+        unsafe {
+            syz_handleIncRef(source);
+            syz_handleIncRef(context);
+        }
+        events.push_back(syz_Event {
+            type_: event_type as c_int,
+            source,
+            context,
+            payload: syz_Event__bindgen_ty_1 {
+                user_automation: syz_UserAutomationEvent { param },
+            },
+        });
+    }
+}
+
+pub unsafe fn syz_contextGetNextEvent(
+    out: *mut syz_Event,
+    context: syz_Handle,
+    _flags: c_ulonglong,
+) -> syz_ErrorCode {
+    if let Err(e) = get(context, -1) {
+        return e;
+    }
+    let event = EVENTS
+        .lock()
+        .unwrap()
+        .get_mut(&context)
+        .and_then(|v| v.pop_front());
+    *out = event.unwrap_or_else(|| std::mem::zeroed());
+    0
+}
+
+pub unsafe fn syz_eventDeinit(event: *mut syz_Event) {
+    syz_handleDecRef((*event).source);
+    syz_handleDecRef((*event).context);
+}
+
+pub unsafe fn syz_registerStreamProtocol(
+    protocol: *const c_char,
+    callback: syz_StreamOpenCallback,
+    userdata: *mut c_void,
+) -> syz_ErrorCode {
+    let protocol = CStr::from_ptr(protocol).to_string_lossy().into_owned();
+    let mut protocols = PROTOCOLS.lock().unwrap();
+    if protocols.contains_key(&protocol) {
+        return fail(&format!(
+            "Attempted duplicate registry of protocol {}",
+            protocol
+        ));
+    }
+    protocols.insert(protocol, (callback, userdata as usize));
+    0
+}
+
+fn custom_stream_error(code: c_int, err_msg: *const c_char) -> syz_ErrorCode {
+    let mut message = format!("Custom byte stream error{}", code);
+    if !err_msg.is_null() {
+        message += &unsafe { CStr::from_ptr(err_msg) }.to_string_lossy();
+    }
+    fail(&message)
+}
+
+unsafe fn close_stream(def: &syz_CustomStreamDef) {
+    if let Some(close) = def.close_cb {
+        close(def.userdata, &mut std::ptr::null());
+        if let Some(destroy) = def.destroy_cb {
+            destroy(def.userdata);
+        }
+    }
+}
+
+/// Reads a stream to the end, then "decodes" it.
+unsafe fn read_stream(protocol: *const c_char, path: *const c_char) -> syz_ErrorCode {
+    let name = CStr::from_ptr(protocol).to_string_lossy().into_owned();
+    if name == "file" {
+        return 0;
+    }
+    let (open, userdata) = match PROTOCOLS.lock().unwrap().get(&name) {
+        Some(v) => *v,
+        None => return fail(&format!("Unregistered protocol {}", name)),
+    };
+    let mut def: syz_CustomStreamDef = std::mem::zeroed();
+    let mut err_msg = std::ptr::null();
+    let code = open.unwrap()(
+        &mut def,
+        protocol,
+        path,
+        std::ptr::null_mut(),
+        userdata as *mut c_void,
+        &mut err_msg,
+    );
+    if code != 0 {
+        return custom_stream_error(code, err_msg);
+    }
+    let mut data = vec![];
+    let mut result = 0;
+    if let Some(seek) = def.seek_cb {
+        let code = seek(0, def.userdata, &mut err_msg);
+        if code != 0 {
+            result = custom_stream_error(code, err_msg);
+        }
+    }
+    while result == 0 {
+        let mut chunk = [0u8; 4];
+        let mut read = 0;
+        let code = def.read_cb.unwrap()(
+            &mut read,
+            chunk.len() as c_ulonglong,
+            chunk.as_mut_ptr() as *mut c_char,
+            def.userdata,
+            &mut err_msg,
+        );
+        if code != 0 {
+            result = custom_stream_error(code, err_msg);
+        }
+        data.extend_from_slice(&chunk[..read as usize]);
+        if (read as usize) < chunk.len() {
+            break;
+        }
+    }
+    close_stream(&def);
+    if result == 0 && !data.starts_with(b"RIFF") {
+        result = fail("Unsupported audio format.");
+    }
+    result
+}
+
+pub unsafe fn syz_createBufferFromStreamParams(
+    out: *mut syz_Handle,
+    protocol: *const c_char,
+    path: *const c_char,
+    _param: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    match read_stream(protocol, path) {
+        0 => create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_BUFFER),
+        e => e,
+    }
+}
+
+/// Just enough of a WAV header for the mock decoder to accept.
+pub(crate) const WAV: &[u8] = b"RIFF\0\0\0\0WAVE";
+
+pub unsafe fn syz_createBufferFromEncodedData(
+    out: *mut syz_Handle,
+    data_len: c_ulonglong,
+    data: *const c_char,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    let data = std::slice::from_raw_parts(data as *const u8, data_len as usize);
+    if !data.starts_with(b"RIFF") {
+        return fail("Unsupported audio format.");
+    }
+    create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_BUFFER)
+}
+
+pub unsafe fn syz_createBufferFromFloatArray(
+    out: *mut syz_Handle,
+    sr: c_uint,
+    channels: c_uint,
+    frames: c_ulonglong,
+    data: *const f32,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    if channels > 16 {
+        return fail("Too many channels");
+    }
+    let samples = if data.is_null() {
+        vec![]
+    } else {
+        std::slice::from_raw_parts(data, frames as usize * channels as usize).to_vec()
+    };
+    match create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_BUFFER) {
+        0 => {
+            ARRAYS.lock().unwrap().insert(*out, (sr, channels, samples));
+            0
+        }
+        e => e,
+    }
+}
+
+// Buffers decoded from streams are a second of stereo.
+fn buffer_format(buffer: syz_Handle) -> Result<(c_uint, c_uint, usize), syz_ErrorCode> {
+    get(buffer, -1)?;
+    Ok(match ARRAYS.lock().unwrap().get(&buffer) {
+        Some((sr, channels, samples)) => (*sr, *channels, samples.len() / *channels as usize),
+        None => (44100, 2, 44100),
+    })
+}
+
+pub unsafe fn syz_bufferGetChannels(out: *mut c_uint, buffer: syz_Handle) -> syz_ErrorCode {
+    match buffer_format(buffer) {
+        Ok((_, channels, _)) => *out = channels,
+        Err(e) => return e,
+    }
+    0
+}
+
+pub unsafe fn syz_bufferGetLengthInSamples(out: *mut c_uint, buffer: syz_Handle) -> syz_ErrorCode {
+    match buffer_format(buffer) {
+        Ok((_, _, frames)) => *out = frames as c_uint,
+        Err(e) => return e,
+    }
+    0
+}
+
+pub unsafe fn syz_bufferGetLengthInSeconds(out: *mut f64, buffer: syz_Handle) -> syz_ErrorCode {
+    match buffer_format(buffer) {
+        Ok((sr, _, frames)) => *out = frames as f64 / sr as f64,
+        Err(e) => return e,
+    }
+    0
+}
+
+pub unsafe fn syz_createStreamingGeneratorFromStreamParams(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    protocol: *const c_char,
+    path: *const c_char,
+    _param: *mut c_void,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    match read_stream(protocol, path) {
+        0 => create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_STREAMING_GENERATOR),
+        e => e,
+    }
+}
+
+pub unsafe fn syz_createBufferGenerator(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_BUFFER_GENERATOR)
+}
+
+/// The waves a sine bank generator was created with.
+pub(crate) fn sine_bank_waves(generator: syz_Handle) -> Vec<crate::SineBankWave> {
+    SINE_BANKS
+        .lock()
+        .unwrap()
+        .get(&generator)
+        .map(|v| {
+            v.iter()
+                .map(|v| crate::SineBankWave::new(v.frequency_mul, v.phase, v.gain))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub unsafe fn syz_createFastSineBankGenerator(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    bank_config: *const syz_SineBankConfig,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    let config = &*bank_config;
+    let result = create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_FAST_SINE_BANK_GENERATOR);
+    if result != 0 {
+        return result;
+    }
+    let waves = std::slice::from_raw_parts(config.waves, config.wave_count as usize);
+    SINE_BANKS.lock().unwrap().insert(*out, waves.to_vec());
+    set(
+        *out,
+        SYZ_PROPERTIES_SYZ_P_FREQUENCY as c_int,
+        Value::D(config.initial_frequency),
+    )
+}
+
+// Synthizer designs the presets itself. Only the number of partials matters
+// to the tests.
+unsafe fn create_sine_bank_preset(
+    out: *mut syz_Handle,
+    initial_frequency: f64,
+    partials: c_uint,
+) -> syz_ErrorCode {
+    let waves = (1..=partials)
+        .map(|v| syz_SineBankWave {
+            frequency_mul: v as f64,
+            phase: 0.,
+            gain: 1. / partials as f64,
+        })
+        .collect::<Vec<_>>();
+    let config = syz_SineBankConfig {
+        waves: waves.as_ptr(),
+        wave_count: waves.len() as u64,
+        initial_frequency,
+    };
+    let null = std::ptr::null_mut();
+    syz_createFastSineBankGenerator(out, 0, &config, null, null, None)
+}
+
+pub unsafe fn syz_createFastSineBankGeneratorSine(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    initial_frequency: f64,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create_sine_bank_preset(out, initial_frequency, 1)
+}
+
+pub unsafe fn syz_createFastSineBankGeneratorTriangle(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    initial_frequency: f64,
+    partials: c_uint,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create_sine_bank_preset(out, initial_frequency, partials)
+}
+
+pub unsafe fn syz_createFastSineBankGeneratorSquare(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    initial_frequency: f64,
+    partials: c_uint,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create_sine_bank_preset(out, initial_frequency, partials)
+}
+
+pub unsafe fn syz_createFastSineBankGeneratorSaw(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    initial_frequency: f64,
+    partials: c_uint,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create_sine_bank_preset(out, initial_frequency, partials)
+}
+
+pub unsafe fn syz_createNoiseGenerator(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    _channels: c_uint,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_NOISE_GENERATOR)
+}
+
+pub unsafe fn syz_createDirectSource(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_DIRECT_SOURCE)
+}
+
+pub unsafe fn syz_createAngularPannedSource(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    _panner_strategy: c_int,
+    _azimuth: f64,
+    _elevation: f64,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_ANGULAR_PANNED_SOURCE)
+}
+
+pub unsafe fn syz_createScalarPannedSource(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    _panner_strategy: c_int,
+    _panning_scalar: f64,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_SCALAR_PANNED_SOURCE)
+}
+
+pub unsafe fn syz_createSource3D(
+    out: *mut syz_Handle,
+    _context: syz_Handle,
+    _panner_strategy: c_int,
+    _x: f64,
+    _y: f64,
+    _z: f64,
+    _config: *mut c_void,
+    _userdata: *mut c_void,
+    _userdata_free_callback: syz_UserdataFreeCallback,
+) -> syz_ErrorCode {
+    create(out, SYZ_OBJECT_TYPE_SYZ_OTYPE_SOURCE_3D)
+}
+
+pub unsafe fn syz_sourceAddGenerator(source: syz_Handle, generator: syz_Handle) -> syz_ErrorCode {
+    match (get(source, -1), get(generator, -1)) {
+        (Ok(_), Ok(_)) => 0,
+        _ => invalid_handle(),
+    }
+}
+
+pub unsafe fn syz_sourceRemoveGenerator(
+    source: syz_Handle,
+    generator: syz_Handle,
+) -> syz_ErrorCode {
+    syz_sourceAddGenerator(source, generator)
+}
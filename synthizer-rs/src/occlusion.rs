@@ -0,0 +1,661 @@
+//! Muffling sounds heard through walls.
+//!
+//! An [`Occluder`] says how much lies between the listener and a source,
+//! usually by raycasting against the game's geometry. Once one is given to a
+//! context with [`Context::set_occluder`], a background thread regularly
+//! asks it about the context's 3D sources, and lowers their gain and direct
+//! path cutoff to match.
+//!
+//! Sources created while the context has an occluder are occluded unless
+//! they opt out with [`Source3D::set_occlusion_enabled`]. Sources created
+//! before must opt in the same way. Occlusion multiplies the gain the
+//! application sets, including fades, rather than replacing it. A direct
+//! path filter set while occluded reads back as set, and takes over once the
+//! source is in the open again.
+//!
+//! Sources moved by a [`Propagation`](crate::Propagation) are occluded from
+//! their real position, not the one they're heard from.
+
+use std::{collections::BTreeMap, fmt, sync::Arc, sync::Mutex, thread, time::Duration};
+
+use crate::{
+    ffi::*,
+    propagation, property,
+    scaled::{self, Layer},
+    BiquadConfig, Context, Handle, Object, Source, Source3D, SynthizerError, WeakHandle,
+};
+
+// The cutoff of a source with nothing in the way, just below Nyquist.
+const OPEN_CUTOFF: f64 = 20000.;
+
+// Occlusion below this leaves the direct path unfiltered.
+const MIN_FILTERED: f64 = 1e-3;
+
+/// Says how much lies between the listener and a source.
+///
+/// This is implemented for every `Fn((f64, f64, f64), (f64, f64, f64)) ->
+/// f64`. It's called from a background thread.
+pub trait Occluder: Send + Sync {
+    /// How occluded a source at `source` is from a listener at `listener`,
+    /// from 0 for a clear line of sight to 1 for fully blocked. Positions
+    /// are in the context's [`CoordinateSystem`](crate::CoordinateSystem).
+    fn occlusion(&self, listener: (f64, f64, f64), source: (f64, f64, f64)) -> f64;
+}
+
+impl<F> Occluder for F
+where
+    F: Fn((f64, f64, f64), (f64, f64, f64)) -> f64 + Send + Sync,
+{
+    fn occlusion(&self, listener: (f64, f64, f64), source: (f64, f64, f64)) -> f64 {
+        self(listener, source)
+    }
+}
+
+/// How occlusion sounds, and how often it's updated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OcclusionConfig {
+    /// What a fully occluded source's gain is multiplied by.
+    pub occluded_gain: f64,
+    /// The lowpass cutoff in Hz of a fully occluded source's direct path.
+    pub occluded_cutoff: f64,
+    /// Seconds for occlusion to get most of the way to a new value, so
+    /// sounds don't snap as things move past corners.
+    pub smoothing: f64,
+    /// Seconds between updates.
+    pub interval: f64,
+}
+
+impl Default for OcclusionConfig {
+    fn default() -> Self {
+        Self {
+            occluded_gain: 0.3,
+            occluded_cutoff: 600.,
+            smoothing: 0.1,
+            interval: 0.05,
+        }
+    }
+}
+
+impl OcclusionConfig {
+    fn validate(&self) -> Result<(), SynthizerError> {
+        // Written so NaNs fail too.
+        let valid = self.occluded_gain >= 0.
+            && self.occluded_gain <= 1.
+            && self.occluded_cutoff > 0.
+            && self.occluded_cutoff <= OPEN_CUTOFF
+            && self.smoothing >= 0.
+            && self.smoothing.is_finite()
+            && self.interval > 0.
+            && self.interval.is_finite();
+        if valid {
+            Ok(())
+        } else {
+            Err(SynthizerError::InvalidArgument(format!(
+                "Invalid occlusion configuration {:?}",
+                self
+            )))
+        }
+    }
+
+    /// What gain is multiplied by at `occlusion`.
+    fn gain(&self, occlusion: f64) -> f64 {
+        if occlusion <= 0. {
+            1.
+        } else {
+            self.occluded_gain.powf(occlusion)
+        }
+    }
+
+    /// The direct path filter at `occlusion`, moving evenly in pitch from
+    /// open to the occluded cutoff, or `None` if it's too low to filter.
+    fn filter(&self, occlusion: f64) -> Result<Option<BiquadConfig>, SynthizerError> {
+        if occlusion < MIN_FILTERED {
+            return Ok(None);
+        }
+        let cutoff = OPEN_CUTOFF.powf(1. - occlusion) * self.occluded_cutoff.powf(occlusion);
+        BiquadConfig::lowpass(cutoff, BiquadConfig::DEFAULT_Q).map(Some)
+    }
+}
+
+/// What occlusion knows about a source.
+#[derive(Clone, Copy, Debug)]
+struct Tracked {
+    enabled: bool,
+    // After smoothing, once occlusion has reached the source.
+    occlusion: Option<f64>,
+    // The application's direct path filter, once occlusion has changed it.
+    filter_direct: Option<BiquadConfig>,
+}
+
+/// A context's occlusion, and the sources it knows about.
+struct OcclusionState {
+    context: WeakHandle,
+    occluder: Option<Arc<dyn Occluder>>,
+    config: OcclusionConfig,
+    // Bumped whenever the occluder changes, stopping older update threads.
+    generation: u64,
+    last_update: Option<f64>,
+    sources: BTreeMap<syz_Handle, (WeakHandle, Tracked)>,
+}
+
+impl fmt::Debug for OcclusionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OcclusionState")
+            .field("occluder", &self.occluder.is_some())
+            .field("config", &self.config)
+            .field("sources", &self.sources.len())
+            .finish()
+    }
+}
+
+// Keyed by context. Entries for dropped contexts are pruned whenever one is
+// added.
+static STATES: Mutex<BTreeMap<syz_Handle, OcclusionState>> = Mutex::new(BTreeMap::new());
+
+fn with_state<T>(context: &Handle, f: impl FnOnce(&mut OcclusionState) -> T) -> T {
+    let mut states = STATES.lock().unwrap();
+    if !states
+        .get(&**context)
+        .is_some_and(|v| v.context.is(context))
+    {
+        states.retain(|_, v| v.context.upgrade().is_some());
+        states.insert(
+            **context,
+            OcclusionState {
+                context: context.downgrade(),
+                occluder: None,
+                config: OcclusionConfig::default(),
+                generation: 0,
+                last_update: None,
+                sources: BTreeMap::new(),
+            },
+        );
+    }
+    f(states.get_mut(&**context).expect("Just inserted"))
+}
+
+/// Runs `f` on what occlusion knows about `source`, if anything.
+fn with_tracked<T>(source: &Handle, f: impl FnOnce(&mut Tracked) -> T) -> Option<T> {
+    let context = source.context()?;
+    let mut states = STATES.lock().unwrap();
+    let state = states
+        .get_mut(&*context)
+        .filter(|v| v.context.is(&context))?;
+    match state.sources.get_mut(&**source) {
+        Some((weak, tracked)) if weak.is(source) => Some(f(tracked)),
+        _ => None,
+    }
+}
+
+fn untracked() -> Tracked {
+    Tracked {
+        enabled: true,
+        occlusion: None,
+        filter_direct: None,
+    }
+}
+
+/// Has `context` occlude a newly created source, if it has an occluder.
+pub(crate) fn track(context: &Handle, source: &Handle) {
+    let mut states = STATES.lock().unwrap();
+    let state = match states.get_mut(&**context) {
+        Some(v) if v.context.is(context) && v.occluder.is_some() => v,
+        _ => return,
+    };
+    state.sources.retain(|_, v| v.0.upgrade().is_some());
+    state
+        .sources
+        .insert(**source, (source.downgrade(), untracked()));
+}
+
+/// Sets the direct path filter Synthizer uses, leaving the application's
+/// alone.
+fn apply_filter(source: &Handle, filter: BiquadConfig) -> Result<(), SynthizerError> {
+    let property = property::FILTER_DIRECT.id();
+    wrap!(unsafe { syz_setBiquad(**source, property, &filter.0) })
+}
+
+/// The application's direct path filter for `source`, if `property` is
+/// that filter and occlusion has changed it.
+pub(crate) fn get_filter(source: &Handle, property: i32) -> Option<BiquadConfig> {
+    if property != property::FILTER_DIRECT.id() {
+        return None;
+    }
+    with_tracked(source, |v| v.filter_direct).flatten()
+}
+
+/// Sets the application's direct path filter for `source` if `property` is
+/// that filter and occlusion has changed it, returning whether occlusion's
+/// filter stays in place. Otherwise the filter should be set directly.
+pub(crate) fn set_filter(source: &Handle, property: i32, filter: BiquadConfig) -> bool {
+    if property != property::FILTER_DIRECT.id() {
+        return false;
+    }
+    with_tracked(source, |v| match v.filter_direct.as_mut() {
+        Some(saved) => {
+            *saved = filter;
+            v.occlusion.is_some_and(|v| v >= MIN_FILTERED)
+        }
+        None => false,
+    })
+    .unwrap_or(false)
+}
+
+/// Puts a source back how it was before occlusion touched it, with any
+/// filter the application set meanwhile.
+fn restore(source: &Source3D) -> Result<(), SynthizerError> {
+    let filter_direct = with_tracked(source.handle(), |v| {
+        v.occlusion = None;
+        v.filter_direct.take()
+    })
+    .flatten();
+    scaled::remove_factor(source.handle(), property::GAIN, Layer::Occlusion)?;
+    if let Some(filter_direct) = filter_direct {
+        apply_filter(source.handle(), filter_direct)?;
+    }
+    Ok(())
+}
+
+fn spawn_updates(context: &Context, generation: u64, interval: f64) {
+    let weak = context.handle().downgrade();
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs_f64(interval));
+        let context = match weak.upgrade() {
+            Some(v) => Context(v),
+            None => return,
+        };
+        if with_state(context.handle(), |v| v.generation) != generation {
+            return;
+        }
+        if let Err(e) = context.update_occlusion() {
+            log::warn!(target: "synthizer", "Error updating occlusion: {}", e);
+        }
+    });
+}
+
+impl Context {
+    /// Starts occluding this context's 3D sources with `occluder`, replacing
+    /// any occluder it had. Sources created before this must opt in with
+    /// [`Source3D::set_occlusion_enabled`].
+    pub fn set_occluder(
+        &self,
+        occluder: impl Occluder + 'static,
+        config: OcclusionConfig,
+    ) -> Result<(), SynthizerError> {
+        config.validate()?;
+        let generation = with_state(self.handle(), |state| {
+            state.occluder = Some(Arc::new(occluder));
+            state.config = config;
+            state.generation += 1;
+            state.generation
+        });
+        spawn_updates(self, generation, config.interval);
+        Ok(())
+    }
+
+    /// Stops occluding, and restores the gain and direct path filter of
+    /// every occluded source.
+    pub fn clear_occluder(&self) -> Result<(), SynthizerError> {
+        let sources = with_state(self.handle(), |state| {
+            state.occluder = None;
+            state.generation += 1;
+            state.last_update = None;
+            state
+                .sources
+                .values()
+                .filter_map(|v| v.0.upgrade())
+                .collect::<Vec<_>>()
+        });
+        for source in sources {
+            restore(&Source3D(source))?;
+        }
+        Ok(())
+    }
+
+    pub fn get_occlusion_config(&self) -> OcclusionConfig {
+        with_state(self.handle(), |v| v.config)
+    }
+
+    /// Updates occlusion now, rather than waiting for the background thread.
+    pub fn update_occlusion(&self) -> Result<(), SynthizerError> {
+        let now = self.get_current_time()?;
+        let update = with_state(self.handle(), |state| {
+            let occluder = state.occluder.clone()?;
+            state.sources.retain(|_, v| v.0.upgrade().is_some());
+            let sources = state
+                .sources
+                .values()
+                .filter(|v| v.1.enabled)
+                .filter_map(|v| v.0.upgrade())
+                .map(Source3D)
+                .collect::<Vec<_>>();
+            let elapsed = state.last_update.map(|v| now - v);
+            state.last_update = Some(now);
+            Some((occluder, state.config, sources, elapsed))
+        });
+        let (occluder, config, sources, elapsed) = match update {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        // How far smoothed occlusion moves towards where it's heading.
+        let step = match elapsed {
+            Some(elapsed) if config.smoothing > 0. => 1. - (-elapsed / config.smoothing).exp(),
+            _ => 1.,
+        };
+        let listener = self.get_position()?;
+        for source in sources.iter() {
+            let position = match propagation::real_position(source.handle()) {
+                Some(v) => v,
+                None => source.get_position()?,
+            };
+            let occlusion = occluder.occlusion(listener, position);
+            let occlusion = if occlusion.is_nan() {
+                0.
+            } else {
+                occlusion.clamp(0., 1.)
+            };
+            let previous = with_tracked(source.handle(), |v| v.occlusion).flatten();
+            let occlusion = match previous {
+                Some(v) => v + (occlusion - v) * step,
+                None => occlusion,
+            };
+            with_tracked(source.handle(), |v| v.occlusion = Some(occlusion));
+            if previous.unwrap_or(0.) == occlusion {
+                continue;
+            }
+            // Reads the application's filter, even if occlusion changed it.
+            let current = source.get_filter_direct()?;
+            // Saved before the filter changes, so the application setting
+            // one from now on only updates what's saved.
+            let saved = with_tracked(source.handle(), |v| *v.filter_direct.get_or_insert(current))
+                .unwrap_or(current);
+            apply_filter(source.handle(), config.filter(occlusion)?.unwrap_or(saved))?;
+            let gain = config.gain(occlusion);
+            scaled::set_factor(
+                source.handle(),
+                property::GAIN,
+                Layer::Occlusion,
+                gain,
+                config.interval,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Source3D {
+    /// Whether occlusion changes this source. Enabling it has the context's
+    /// occluder, now or later, reach a source created before it had one.
+    /// Disabling it restores the source's gain and direct path filter.
+    pub fn set_occlusion_enabled(&self, enabled: bool) -> Result<(), SynthizerError> {
+        let context = self.handle().context().ok_or_else(|| {
+            SynthizerError::InvalidArgument("Source has no live context".to_string())
+        })?;
+        with_state(&context, |state| {
+            let raw = **self.handle();
+            let known = state
+                .sources
+                .get(&raw)
+                .is_some_and(|v| v.0.is(self.handle()));
+            if !known {
+                state.sources.retain(|_, v| v.0.upgrade().is_some());
+                state
+                    .sources
+                    .insert(raw, (self.handle().downgrade(), untracked()));
+            }
+            state
+                .sources
+                .get_mut(&raw)
+                .expect("Just inserted")
+                .1
+                .enabled = enabled;
+        });
+        if !enabled {
+            restore(self)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_occlusion_enabled(&self) -> bool {
+        with_tracked(self.handle(), |v| v.enabled).unwrap_or(false)
+    }
+
+    /// How occluded this source currently sounds, after smoothing.
+    pub fn get_occlusion(&self) -> f64 {
+        with_tracked(self.handle(), |v| v.occlusion)
+            .flatten()
+            .unwrap_or(0.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{context::BLOCK_SIZE, mock, Synthizer, SAMPLE_RATE};
+
+    // Far longer than any test, so only explicit updates happen.
+    const MANUAL: OcclusionConfig = OcclusionConfig {
+        occluded_gain: 0.25,
+        occluded_cutoff: 500.,
+        smoothing: 0.,
+        interval: 3600.,
+    };
+
+    // A wall along y = 5.
+    fn wall(listener: (f64, f64, f64), source: (f64, f64, f64)) -> f64 {
+        if (listener.1 < 5.) == (source.1 < 5.) {
+            0.
+        } else {
+            1.
+        }
+    }
+
+    /// The direct path filter Synthizer uses, whatever the application set.
+    fn applied_filter(source: &Source3D) -> BiquadConfig {
+        let mut out = unsafe { std::mem::zeroed::<syz_BiquadConfig>() };
+        let property = property::FILTER_DIRECT.id();
+        unsafe { syz_getBiquad(&mut out, **source.handle(), property) };
+        BiquadConfig(out)
+    }
+
+    fn last_gain(source: &Source3D) -> Option<f64> {
+        let points = mock::automation(**source.handle(), property::GAIN.id());
+        points.last().map(|v| v.2[0])
+    }
+
+    #[test]
+    fn maps_occlusion_to_gain_and_cutoff() -> Result<(), SynthizerError> {
+        assert_eq!(MANUAL.gain(0.), 1.);
+        assert_eq!(MANUAL.gain(1.), 0.25);
+        assert!((MANUAL.gain(0.5) - 0.5).abs() < 1e-9);
+        assert_eq!(MANUAL.filter(0.)?, None);
+        assert_eq!(
+            MANUAL.filter(1.)?,
+            Some(BiquadConfig::lowpass(500., BiquadConfig::DEFAULT_Q)?)
+        );
+        let middle = (OPEN_CUTOFF * 500f64).sqrt();
+        let half = MANUAL.filter(0.5)?.unwrap();
+        let expected = BiquadConfig::lowpass(middle, BiquadConfig::DEFAULT_Q)?;
+        assert!((half.0._b0 - expected.0._b0).abs() < 1e-12);
+        let bad = OcclusionConfig {
+            occluded_gain: 2.,
+            ..MANUAL
+        };
+        assert!(bad.validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn occludes_sources_behind_walls() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        context.set_occluder(wall, MANUAL)?;
+        assert_eq!(context.get_occlusion_config(), MANUAL);
+        let behind = context.new_source3d()?;
+        behind.set_position(0., 10., 0.)?;
+        behind.set_gain(0.8)?;
+        let muffled = BiquadConfig::lowpass(5000., BiquadConfig::DEFAULT_Q)?;
+        behind.set_filter_direct(muffled)?;
+        let open = context.new_source3d()?;
+        open.set_position(0., 1., 0.)?;
+        let ignored = context.new_source3d()?;
+        ignored.set_position(0., 10., 0.)?;
+        ignored.set_occlusion_enabled(false)?;
+        context.update_occlusion()?;
+        assert_eq!(behind.get_occlusion(), 1.);
+        assert_eq!(last_gain(&behind), Some(0.2));
+        assert_eq!(
+            applied_filter(&behind),
+            BiquadConfig::lowpass(500., BiquadConfig::DEFAULT_Q)?
+        );
+        assert_eq!(behind.get_filter_direct()?, muffled);
+        assert_eq!(open.get_occlusion(), 0.);
+        assert_eq!(last_gain(&open), None);
+        assert!(!ignored.is_occlusion_enabled());
+        assert_eq!(last_gain(&ignored), None);
+        behind.set_position(0., 2., 0.)?;
+        context.update_occlusion()?;
+        assert_eq!(last_gain(&behind), Some(0.8));
+        assert_eq!(applied_filter(&behind), muffled);
+        behind.set_position(0., 10., 0.)?;
+        context.update_occlusion()?;
+        context.clear_occluder()?;
+        assert_eq!(last_gain(&behind), None);
+        assert_eq!(behind.get_gain()?, 0.8);
+        assert_eq!(applied_filter(&behind), muffled);
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_filter_set_while_occluded() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        context.set_occluder(wall, MANUAL)?;
+        let source = context.new_source3d()?;
+        source.set_position(0., 10., 0.)?;
+        context.update_occlusion()?;
+        let occluded = BiquadConfig::lowpass(500., BiquadConfig::DEFAULT_Q)?;
+        let muffled = BiquadConfig::lowpass(5000., BiquadConfig::DEFAULT_Q)?;
+        source.set_filter_direct(muffled)?;
+        assert_eq!(applied_filter(&source), occluded);
+        assert_eq!(source.get_filter_direct()?, muffled);
+        // Out in the open, the application's filter applies straight away.
+        source.set_position(0., 2., 0.)?;
+        context.update_occlusion()?;
+        assert_eq!(applied_filter(&source), muffled);
+        let bright = BiquadConfig::lowpass(10000., BiquadConfig::DEFAULT_Q)?;
+        source.set_filter_direct(bright)?;
+        assert_eq!(applied_filter(&source), bright);
+        source.set_position(0., 10., 0.)?;
+        context.update_occlusion()?;
+        source.set_filter_direct(muffled)?;
+        context.clear_occluder()?;
+        assert_eq!(applied_filter(&source), muffled);
+        assert_eq!(source.get_filter_direct()?, muffled);
+        Ok(())
+    }
+
+    #[test]
+    fn scales_the_gain_set_while_occluded() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        context.set_occluder(wall, MANUAL)?;
+        let source = context.new_source3d()?;
+        source.set_position(0., 10., 0.)?;
+        context.update_occlusion()?;
+        source.set_gain(0.4)?;
+        assert_eq!(source.get_gain()?, 0.4);
+        assert_eq!(last_gain(&source), Some(0.1));
+        source.fade_to(0.8, Duration::from_millis(100))?;
+        assert_eq!(last_gain(&source), Some(0.2));
+        context.update_occlusion()?;
+        assert_eq!(last_gain(&source), Some(0.2));
+        // The fade carries on unoccluded.
+        context.clear_occluder()?;
+        assert_eq!(last_gain(&source), Some(0.8));
+        Ok(())
+    }
+
+    #[test]
+    fn only_tracks_sources_which_can_be_occluded() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let before = context.new_source3d()?;
+        before.set_position(0., 10., 0.)?;
+        assert!(!STATES.lock().unwrap().contains_key(&**context.handle()));
+        context.set_occluder(wall, MANUAL)?;
+        context.update_occlusion()?;
+        assert!(!before.is_occlusion_enabled());
+        assert_eq!(before.get_occlusion(), 0.);
+        before.set_occlusion_enabled(true)?;
+        context.update_occlusion()?;
+        assert_eq!(before.get_occlusion(), 1.);
+        Ok(())
+    }
+
+    #[test]
+    fn occludes_propagated_sources_where_they_are() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        context.set_occluder(wall, MANUAL)?;
+        let source = context.new_source3d()?;
+        let grid = crate::PropagationGrid::from_rows(&["...", "...", "..."], '#', 1.)?;
+        let mut propagation = context.new_propagation(grid, Default::default())?;
+        propagation.set_source_position(&source, 0.5, 10., 0.)?;
+        // Heard from the open side of the wall, but really behind it.
+        source.set_position(0.5, 1., 0.)?;
+        context.update_occlusion()?;
+        assert_eq!(source.get_occlusion(), 1.);
+        drop(propagation);
+        context.update_occlusion()?;
+        assert_eq!(source.get_occlusion(), 0.);
+        Ok(())
+    }
+
+    #[test]
+    fn smooths_changes() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let config = OcclusionConfig {
+            smoothing: 0.1,
+            ..MANUAL
+        };
+        context.set_occluder(wall, config)?;
+        let source = context.new_source3d()?;
+        source.set_position(0., 1., 0.)?;
+        context.update_occlusion()?;
+        source.set_position(0., 10., 0.)?;
+        context.render_frames(BLOCK_SIZE * 16)?;
+        context.update_occlusion()?;
+        let elapsed = (BLOCK_SIZE * 16) as f64 / SAMPLE_RATE as f64;
+        let expected = 1. - (-elapsed / 0.1).exp();
+        assert!((source.get_occlusion() - expected).abs() < 1e-9);
+        context.render_frames(SAMPLE_RATE as usize)?;
+        context.update_occlusion()?;
+        assert!(source.get_occlusion() > 0.99);
+        Ok(())
+    }
+
+    #[test]
+    fn updates_in_the_background() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        source.set_position(0., 10., 0.)?;
+        source.set_occlusion_enabled(true)?;
+        let config = OcclusionConfig {
+            interval: 0.005,
+            ..MANUAL
+        };
+        context.set_occluder(|_, _| 1., config)?;
+        let start = Instant::now();
+        while source.get_occlusion() != 1. && start.elapsed() < Duration::from_secs(2) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(source.get_occlusion(), 1.);
+        context.clear_occluder()?;
+        Ok(())
+    }
+}
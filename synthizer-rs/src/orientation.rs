@@ -0,0 +1,632 @@
+//! Orientations and coordinate conventions.
+//!
+//! Synthizer's coordinates are right-handed, with +x to the right, +y forward
+//! and +z up. Engines using other conventions can give their context a
+//! [`CoordinateSystem`], after which the positions and orientations of the
+//! context and its 3D sources are in the engine's coordinates, and converted
+//! on their way to and from Synthizer. This covers every way of setting them,
+//! including [`Object::set`](crate::Object::set) and automation.
+
+use crate::{property, Context, Handle, Object, SynthizerError};
+
+pub(crate) type V = [f64; 3];
+
+fn cross(a: V, b: V) -> V {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: V) -> Option<V> {
+    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    // Written so NaNs fail too.
+    let valid = length > f64::EPSILON && length.is_finite();
+    if valid {
+        Some([v[0] / length, v[1] / length, v[2] / length])
+    } else {
+        None
+    }
+}
+
+fn normalize_quaternion([w, x, y, z]: [f64; 4]) -> Option<[f64; 4]> {
+    let length = (w * w + x * x + y * y + z * z).sqrt();
+    let valid = length > f64::EPSILON && length.is_finite();
+    if valid {
+        Some([w / length, x / length, y / length, z / length])
+    } else {
+        None
+    }
+}
+
+fn rotate([w, x, y, z]: [f64; 4], v: V) -> V {
+    let u = [x, y, z];
+    let t = cross(u, v);
+    let t = [2. * t[0], 2. * t[1], 2. * t[2]];
+    let c = cross(u, t);
+    [
+        v[0] + w * t[0] + c[0],
+        v[1] + w * t[1] + c[1],
+        v[2] + w * t[2] + c[2],
+    ]
+}
+
+fn multiply([w1, x1, y1, z1]: [f64; 4], [w2, x2, y2, z2]: [f64; 4]) -> [f64; 4] {
+    [
+        w1 * w2 - x1 * x2 - y1 * y2 - z1 * z2,
+        w1 * x2 + x1 * w2 + y1 * z2 - z1 * y2,
+        w1 * y2 - x1 * z2 + y1 * w2 + z1 * x2,
+        w1 * z2 + x1 * y2 - y1 * x2 + z1 * w2,
+    ]
+}
+
+/// Makes `up` perpendicular to `at`, and both unit length.
+fn orthonormalize(at: V, up: V) -> Option<(V, V)> {
+    let at = normalize(at)?;
+    let right = normalize(cross(at, up))?;
+    Some((at, cross(right, at)))
+}
+
+fn degenerate(at: V, up: V) -> SynthizerError {
+    SynthizerError::InvalidArgument(format!("Can't orient towards {:?} with up {:?}", at, up))
+}
+
+/// The rotation taking +y to `at` and +z to `up`.
+fn quaternion_from_at_up(at: V, up: V) -> Option<[f64; 4]> {
+    let (f, u) = orthonormalize(at, up)?;
+    let r = cross(f, u);
+    // The rotation matrix's columns are where x, y and z end up.
+    let m = [[r[0], f[0], u[0]], [r[1], f[1], u[1]], [r[2], f[2], u[2]]];
+    let trace = m[0][0] + m[1][1] + m[2][2];
+    let q = if trace > 0. {
+        let s = (trace + 1.).sqrt() * 2.;
+        [
+            s / 4.,
+            (m[2][1] - m[1][2]) / s,
+            (m[0][2] - m[2][0]) / s,
+            (m[1][0] - m[0][1]) / s,
+        ]
+    } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+        let s = (1. + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.;
+        [
+            (m[2][1] - m[1][2]) / s,
+            s / 4.,
+            (m[0][1] + m[1][0]) / s,
+            (m[0][2] + m[2][0]) / s,
+        ]
+    } else if m[1][1] > m[2][2] {
+        let s = (1. + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.;
+        [
+            (m[0][2] - m[2][0]) / s,
+            (m[0][1] + m[1][0]) / s,
+            s / 4.,
+            (m[1][2] + m[2][1]) / s,
+        ]
+    } else {
+        let s = (1. + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.;
+        [
+            (m[1][0] - m[0][1]) / s,
+            (m[0][2] + m[2][0]) / s,
+            (m[1][2] + m[2][1]) / s,
+            s / 4.,
+        ]
+    };
+    Some(q)
+}
+
+fn to_array((x, y, z): (f64, f64, f64)) -> V {
+    [x, y, z]
+}
+
+fn to_tuple([x, y, z]: V) -> (f64, f64, f64) {
+    (x, y, z)
+}
+
+/// One of the six directions along a coordinate axis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    NegX,
+    Y,
+    NegY,
+    Z,
+    NegZ,
+}
+
+impl Axis {
+    fn index(self) -> usize {
+        match self {
+            Axis::X | Axis::NegX => 0,
+            Axis::Y | Axis::NegY => 1,
+            Axis::Z | Axis::NegZ => 2,
+        }
+    }
+
+    fn sign(self) -> f64 {
+        match self {
+            Axis::X | Axis::Y | Axis::Z => 1.,
+            Axis::NegX | Axis::NegY | Axis::NegZ => -1.,
+        }
+    }
+
+    fn vector(self) -> V {
+        let mut v = [0.; 3];
+        v[self.index()] = self.sign();
+        v
+    }
+}
+
+/// Which axes point right, forward and up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CoordinateSystem {
+    right: Axis,
+    forward: Axis,
+    up: Axis,
+}
+
+impl CoordinateSystem {
+    /// Synthizer's own: +x right, +y forward and +z up.
+    pub const SYNTHIZER: Self = Self {
+        right: Axis::X,
+        forward: Axis::Y,
+        up: Axis::Z,
+    };
+
+    /// +x right, -z forward and +y up, as in OpenGL and glTF.
+    pub const Y_UP_RIGHT_HANDED: Self = Self {
+        right: Axis::X,
+        forward: Axis::NegZ,
+        up: Axis::Y,
+    };
+
+    /// +x right, +z forward and +y up, as in Unity and Direct3D.
+    pub const Y_UP_LEFT_HANDED: Self = Self {
+        right: Axis::X,
+        forward: Axis::Z,
+        up: Axis::Y,
+    };
+
+    /// +y right, +x forward and +z up, as in Unreal.
+    pub const Z_UP_LEFT_HANDED: Self = Self {
+        right: Axis::Y,
+        forward: Axis::X,
+        up: Axis::Z,
+    };
+
+    /// Fails unless the three axes are different.
+    pub fn new(right: Axis, forward: Axis, up: Axis) -> Result<Self, SynthizerError> {
+        let (r, f, u) = (right.index(), forward.index(), up.index());
+        if r == f || f == u || u == r {
+            return Err(SynthizerError::InvalidArgument(format!(
+                "{:?}, {:?} and {:?} don't make a coordinate system",
+                right, forward, up
+            )));
+        }
+        Ok(Self { right, forward, up })
+    }
+
+    pub fn right(&self) -> Axis {
+        self.right
+    }
+
+    pub fn forward(&self) -> Axis {
+        self.forward
+    }
+
+    pub fn up(&self) -> Axis {
+        self.up
+    }
+
+    pub fn is_right_handed(&self) -> bool {
+        let up = cross(self.right.vector(), self.forward.vector());
+        up == self.up.vector()
+    }
+
+    /// Converts a vector from these coordinates to Synthizer's.
+    pub fn to_synthizer(&self, (x, y, z): (f64, f64, f64)) -> (f64, f64, f64) {
+        let v = [x, y, z];
+        let component = |axis: Axis| v[axis.index()] * axis.sign();
+        (
+            component(self.right),
+            component(self.forward),
+            component(self.up),
+        )
+    }
+
+    /// Converts a vector from Synthizer's coordinates to these.
+    pub fn from_synthizer(&self, (x, y, z): (f64, f64, f64)) -> (f64, f64, f64) {
+        let mut v = [0.; 3];
+        for (axis, value) in [(self.right, x), (self.forward, y), (self.up, z)].iter() {
+            v[axis.index()] = value * axis.sign();
+        }
+        to_tuple(v)
+    }
+
+    /// Where `rotation` turns the default facing, in these coordinates.
+    pub(crate) fn rotate(&self, rotation: [f64; 4]) -> Result<(V, V), SynthizerError> {
+        let q = normalize_quaternion(rotation).ok_or_else(|| {
+            SynthizerError::InvalidArgument(format!("Can't orient by quaternion {:?}", rotation))
+        })?;
+        Ok((
+            rotate(q, self.forward.vector()),
+            rotate(q, self.up.vector()),
+        ))
+    }
+
+    /// The rotation turning the default facing towards `at` with `up` up,
+    /// in these coordinates.
+    pub(crate) fn rotation(&self, at: V, up: V) -> Result<[f64; 4], SynthizerError> {
+        let target = quaternion_from_at_up(at, up).ok_or_else(|| degenerate(at, up))?;
+        let [w, x, y, z] = quaternion_from_at_up(self.forward.vector(), self.up.vector())
+            .expect("Axes are perpendicular");
+        Ok(multiply(target, [w, -x, -y, -z]))
+    }
+}
+
+/// Converts the vectors in a value of `property` with `convert`, if it's a
+/// position or orientation.
+fn convert_values(
+    handle: &Handle,
+    property: i32,
+    mut values: [f64; 6],
+    convert: impl Fn(&CoordinateSystem, (f64, f64, f64)) -> (f64, f64, f64),
+) -> [f64; 6] {
+    let vectors = if property == property::POSITION.id() {
+        1
+    } else if property == property::ORIENTATION.id() {
+        2
+    } else {
+        return values;
+    };
+    let system = handle.coordinate_system();
+    for v in values.chunks_mut(3).take(vectors) {
+        let (x, y, z) = convert(&system, (v[0], v[1], v[2]));
+        v.copy_from_slice(&[x, y, z]);
+    }
+    values
+}
+
+/// Converts a value of `property` on `handle` from its context's coordinates
+/// to Synthizer's.
+pub(crate) fn values_to_synthizer(handle: &Handle, property: i32, values: [f64; 6]) -> [f64; 6] {
+    convert_values(handle, property, values, CoordinateSystem::to_synthizer)
+}
+
+/// Converts a value of `property` on `handle` from Synthizer's coordinates to
+/// its context's.
+pub(crate) fn values_from_synthizer(handle: &Handle, property: i32, values: [f64; 6]) -> [f64; 6] {
+    convert_values(handle, property, values, CoordinateSystem::from_synthizer)
+}
+
+impl Default for CoordinateSystem {
+    fn default() -> Self {
+        Self::SYNTHIZER
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Facing {
+    // Along the listener's own right, forward and up, which are Synthizer's
+    // axes whatever the coordinate system.
+    Relative(V, V),
+    // In the context's coordinates.
+    Vectors(V, V),
+    Rotation([f64; 4]),
+}
+
+/// Which way a listener faces, interpreted in its context's
+/// [`CoordinateSystem`] when set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ListenerOrientation(Facing);
+
+impl ListenerOrientation {
+    /// Faces along `at`, with `up` towards the top of the head. `up` is made
+    /// perpendicular to `at`, so only needs to be roughly right.
+    pub fn new(at: (f64, f64, f64), up: (f64, f64, f64)) -> Result<Self, SynthizerError> {
+        let (at, up) = (to_array(at), to_array(up));
+        let (at, up) = orthonormalize(at, up).ok_or_else(|| degenerate(at, up))?;
+        Ok(Self(Facing::Vectors(at, up)))
+    }
+
+    /// Faces from `from` towards `target`.
+    pub fn looking_at(
+        from: (f64, f64, f64),
+        target: (f64, f64, f64),
+        up: (f64, f64, f64),
+    ) -> Result<Self, SynthizerError> {
+        Self::new(
+            (target.0 - from.0, target.1 - from.1, target.2 - from.2),
+            up,
+        )
+    }
+
+    /// Rotates the coordinate system's default facing by the quaternion
+    /// `w + xi + yj + zk`, which needn't be normalized.
+    pub fn from_quaternion(w: f64, x: f64, y: f64, z: f64) -> Result<Self, SynthizerError> {
+        let q = normalize_quaternion([w, x, y, z]).ok_or_else(|| {
+            SynthizerError::InvalidArgument(format!(
+                "Can't orient by quaternion {:?}",
+                [w, x, y, z]
+            ))
+        })?;
+        Ok(Self(Facing::Rotation(q)))
+    }
+
+    /// Turns right by `yaw`, then looks up by `pitch`, then tilts the head
+    /// right by `roll`, all in radians, from the default facing.
+    ///
+    /// These are the same in every coordinate system, so a positive yaw
+    /// turns right even where rotations are usually counterclockwise.
+    pub fn from_yaw_pitch_roll(yaw: f64, pitch: f64, roll: f64) -> Self {
+        let (sy, cy) = yaw.sin_cos();
+        let (sp, cp) = pitch.sin_cos();
+        let (sr, cr) = roll.sin_cos();
+        let at = [sy * cp, cy * cp, sp];
+        let level_up = [-sy * sp, -cy * sp, cp];
+        let right = [cy, -sy, 0.];
+        let up = [
+            level_up[0] * cr + right[0] * sr,
+            level_up[1] * cr + right[1] * sr,
+            level_up[2] * cr + right[2] * sr,
+        ];
+        Self(Facing::Relative(at, up))
+    }
+
+    /// Like [`from_yaw_pitch_roll`](Self::from_yaw_pitch_roll), in degrees.
+    pub fn from_yaw_pitch_roll_degrees(yaw: f64, pitch: f64, roll: f64) -> Self {
+        Self::from_yaw_pitch_roll(yaw.to_radians(), pitch.to_radians(), roll.to_radians())
+    }
+
+    /// The at and up vectors in Synthizer's coordinates.
+    fn to_synthizer(self, system: &CoordinateSystem) -> Result<(V, V), SynthizerError> {
+        let convert = |(at, up): (V, V)| {
+            (
+                to_array(system.to_synthizer(to_tuple(at))),
+                to_array(system.to_synthizer(to_tuple(up))),
+            )
+        };
+        Ok(match self.0 {
+            Facing::Relative(at, up) => (at, up),
+            Facing::Vectors(at, up) => convert((at, up)),
+            Facing::Rotation(q) => convert(system.rotate(q)?),
+        })
+    }
+
+    /// The rotation of `system`'s default facing to this one, as the
+    /// quaternion `(w, x, y, z)`.
+    pub fn quaternion(
+        &self,
+        system: &CoordinateSystem,
+    ) -> Result<(f64, f64, f64, f64), SynthizerError> {
+        let (at, up) = self.at_up(system)?;
+        let [w, x, y, z] = system.rotation(to_array(at), to_array(up))?;
+        Ok((w, x, y, z))
+    }
+
+    /// The at and up vectors in `system`'s coordinates.
+    #[allow(clippy::type_complexity)]
+    pub fn at_up(
+        &self,
+        system: &CoordinateSystem,
+    ) -> Result<((f64, f64, f64), (f64, f64, f64)), SynthizerError> {
+        let (at, up) = self.to_synthizer(system)?;
+        Ok((
+            system.from_synthizer(to_tuple(at)),
+            system.from_synthizer(to_tuple(up)),
+        ))
+    }
+}
+
+impl Context {
+    /// Sets the coordinate system this context's positions and orientations,
+    /// and those of its 3D sources, are given in.
+    ///
+    /// Positions and orientations already set aren't converted, so this is
+    /// best done before placing anything.
+    pub fn set_coordinate_system(&self, system: CoordinateSystem) {
+        self.handle().set_coordinate_system(system);
+    }
+
+    pub fn get_coordinate_system(&self) -> CoordinateSystem {
+        self.handle().coordinate_system()
+    }
+
+    pub fn set_listener_orientation(
+        &self,
+        orientation: ListenerOrientation,
+    ) -> Result<(), SynthizerError> {
+        let ((x1, y1, z1), (x2, y2, z2)) = orientation.at_up(&self.get_coordinate_system())?;
+        self.set_orientation(x1, y1, z1, x2, y2, z2)
+    }
+
+    pub fn get_listener_orientation(&self) -> Result<ListenerOrientation, SynthizerError> {
+        let (x1, y1, z1, x2, y2, z2) = self.get_orientation()?;
+        ListenerOrientation::new((x1, y1, z1), (x2, y2, z2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock, property, Synthizer};
+
+    fn close(a: (f64, f64, f64), b: (f64, f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9 && (a.2 - b.2).abs() < 1e-9
+    }
+
+    fn synthizer_at_up(orientation: ListenerOrientation) -> ((f64, f64, f64), (f64, f64, f64)) {
+        orientation.at_up(&CoordinateSystem::SYNTHIZER).unwrap()
+    }
+
+    #[test]
+    fn turns_by_yaw_pitch_and_roll() {
+        let (at, up) = synthizer_at_up(ListenerOrientation::from_yaw_pitch_roll(0., 0., 0.));
+        assert!(close(at, (0., 1., 0.)) && close(up, (0., 0., 1.)));
+        let (at, up) = synthizer_at_up(ListenerOrientation::from_yaw_pitch_roll_degrees(
+            90., 0., 0.,
+        ));
+        assert!(close(at, (1., 0., 0.)) && close(up, (0., 0., 1.)));
+        let (at, up) = synthizer_at_up(ListenerOrientation::from_yaw_pitch_roll_degrees(
+            0., 90., 0.,
+        ));
+        assert!(close(at, (0., 0., 1.)) && close(up, (0., -1., 0.)));
+        let (at, up) = synthizer_at_up(ListenerOrientation::from_yaw_pitch_roll_degrees(
+            0., 0., 90.,
+        ));
+        assert!(close(at, (0., 1., 0.)) && close(up, (1., 0., 0.)));
+        let (at, _) = synthizer_at_up(ListenerOrientation::from_yaw_pitch_roll_degrees(
+            -90., 45., 0.,
+        ));
+        let h = std::f64::consts::FRAC_1_SQRT_2;
+        assert!(close(at, (-h, 0., h)));
+    }
+
+    #[test]
+    fn builds_from_vectors_and_quaternions() -> Result<(), SynthizerError> {
+        let (at, up) = synthizer_at_up(ListenerOrientation::new((2., 0., 0.), (1., 0., 1.))?);
+        assert!(close(at, (1., 0., 0.)) && close(up, (0., 0., 1.)));
+        let looking = ListenerOrientation::looking_at((1., 1., 0.), (1., 1., -5.), (0., 1., 0.))?;
+        let (at, up) = synthizer_at_up(looking);
+        assert!(close(at, (0., 0., -1.)) && close(up, (0., 1., 0.)));
+        // A quarter turn about +z, which is to the left in Synthizer.
+        let half = std::f64::consts::FRAC_PI_4;
+        let left = ListenerOrientation::from_quaternion(half.cos(), 0., 0., half.sin())?;
+        let (at, up) = synthizer_at_up(left);
+        assert!(close(at, (-1., 0., 0.)) && close(up, (0., 0., 1.)));
+        let (w, x, y, z) = left.quaternion(&CoordinateSystem::SYNTHIZER)?;
+        assert!((w - half.cos()).abs() < 1e-9 && (z - half.sin()).abs() < 1e-9);
+        assert!(x.abs() < 1e-9 && y.abs() < 1e-9);
+        assert!(ListenerOrientation::new((0., 0., 1.), (0., 0., 2.)).is_err());
+        assert!(ListenerOrientation::looking_at((1., 2., 3.), (1., 2., 3.), (0., 0., 1.)).is_err());
+        assert!(ListenerOrientation::from_quaternion(0., 0., 0., 0.).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn converts_between_coordinate_systems() -> Result<(), SynthizerError> {
+        let systems = [
+            CoordinateSystem::SYNTHIZER,
+            CoordinateSystem::Y_UP_RIGHT_HANDED,
+            CoordinateSystem::Y_UP_LEFT_HANDED,
+            CoordinateSystem::Z_UP_LEFT_HANDED,
+        ];
+        let handedness = systems
+            .iter()
+            .map(|v| v.is_right_handed())
+            .collect::<Vec<_>>();
+        assert_eq!(handedness, [true, true, false, false]);
+        let gl = CoordinateSystem::Y_UP_RIGHT_HANDED;
+        assert_eq!(gl.to_synthizer((0., 0., -1.)), (0., 1., 0.));
+        assert_eq!(gl.to_synthizer((0., 1., 0.)), (0., 0., 1.));
+        for system in systems.iter() {
+            let v = (1., -2., 3.);
+            assert_eq!(system.from_synthizer(system.to_synthizer(v)), v);
+        }
+        let custom = CoordinateSystem::new(Axis::NegX, Axis::Z, Axis::Y)?;
+        assert!(custom.is_right_handed());
+        assert!(CoordinateSystem::new(Axis::X, Axis::NegX, Axis::Y).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rotations_round_trip() -> Result<(), SynthizerError> {
+        let systems = [
+            CoordinateSystem::SYNTHIZER,
+            CoordinateSystem::Y_UP_LEFT_HANDED,
+            CoordinateSystem::Z_UP_LEFT_HANDED,
+        ];
+        for system in systems.iter() {
+            let [w, x, y, z] = system.rotation(system.forward.vector(), system.up.vector())?;
+            assert!(close((x, y, z), (0., 0., 0.)) && (w.abs() - 1.).abs() < 1e-9);
+            let q = normalize_quaternion([0.9, 0.1, -0.3, 0.2]).unwrap();
+            let (at, up) = system.rotate(q)?;
+            let back = system.rotation(at, up)?;
+            let same = (0..4).all(|i| (back[i] - q[i]).abs() < 1e-9)
+                || (0..4).all(|i| (back[i] + q[i]).abs() < 1e-9);
+            assert!(same);
+        }
+        Ok(())
+    }
+
+    fn raw_d3(object: &impl Object, property: i32) -> (f64, f64, f64) {
+        let (mut x, mut y, mut z) = (0., 0., 0.);
+        unsafe { crate::ffi::syz_getD3(&mut x, &mut y, &mut z, **object.handle(), property) };
+        (x, y, z)
+    }
+
+    fn raw_d6(object: &impl Object, property: i32) -> (f64, f64, f64, f64, f64, f64) {
+        let (mut x1, mut y1, mut z1) = (0., 0., 0.);
+        let (mut x2, mut y2, mut z2) = (0., 0., 0.);
+        unsafe {
+            crate::ffi::syz_getD6(
+                &mut x1,
+                &mut y1,
+                &mut z1,
+                &mut x2,
+                &mut y2,
+                &mut z2,
+                **object.handle(),
+                property,
+            )
+        };
+        (x1, y1, z1, x2, y2, z2)
+    }
+
+    #[test]
+    fn contexts_convert_positions() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        context.set_coordinate_system(CoordinateSystem::Y_UP_LEFT_HANDED);
+        assert_eq!(
+            context.get_coordinate_system(),
+            CoordinateSystem::Y_UP_LEFT_HANDED
+        );
+        let (position, orientation) = (property::POSITION.id(), property::ORIENTATION.id());
+        source.set_position(1., 2., 3.)?;
+        assert_eq!(raw_d3(&source, position), (1., 3., 2.));
+        assert_eq!(source.get_position()?, (1., 2., 3.));
+        context.set(property::POSITION, (4., 5., 6.))?;
+        assert_eq!(raw_d3(&context, position), (4., 6., 5.));
+        assert_eq!(context.get(property::POSITION)?, (4., 5., 6.));
+        context.set_listener_orientation(ListenerOrientation::from_quaternion(1., 0., 0., 0.)?)?;
+        assert_eq!(raw_d6(&context, orientation), (0., 1., 0., 0., 0., 1.));
+        let looking = ListenerOrientation::looking_at((0., 0., 0.), (1., 0., 0.), (0., 1., 0.))?;
+        context.set_listener_orientation(looking)?;
+        assert_eq!(raw_d6(&context, orientation), (1., 0., 0., 0., 0., 1.));
+        assert_eq!(context.get_orientation()?, (1., 0., 0., 0., 1., 0.));
+        assert_eq!(
+            context.get(property::ORIENTATION)?,
+            (1., 0., 0., 0., 1., 0.)
+        );
+        assert_eq!(context.get_listener_orientation()?, looking);
+        Ok(())
+    }
+
+    #[test]
+    fn automation_converts_positions() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        context.set_coordinate_system(CoordinateSystem::Z_UP_LEFT_HANDED);
+        let mut batch = context.new_automation_batch()?;
+        batch
+            .set(&source, property::POSITION, 0., (1., 2., 3.))?
+            .set(
+                &context,
+                property::ORIENTATION,
+                0.,
+                (1., 0., 0., 0., 0., 1.),
+            )?
+            .set(&source, property::GAIN, 0., 0.5)?;
+        batch.execute()?;
+        let points = mock::automation(**source.handle(), property::POSITION.id());
+        assert_eq!(points[0].2[..3], [2., 1., 3.]);
+        let points = mock::automation(**context.handle(), property::ORIENTATION.id());
+        assert_eq!(points[0].2, [0., 1., 0., 0., 0., 1.]);
+        let points = mock::automation(**source.handle(), property::GAIN.id());
+        assert_eq!(points[0].2[0], 0.5);
+        Ok(())
+    }
+}
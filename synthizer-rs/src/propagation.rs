@@ -0,0 +1,755 @@
+//! Sound travelling around corners on tile maps.
+//!
+//! A [`PropagationGrid`] marks which cells sound can pass through. From the
+//! listener's cell, the shortest walkable path to each source is found, and
+//! the source is heard from the way that path leaves the listener, as far
+//! away as the path is long. A sound in the next room then comes through the
+//! doorway, and gets quieter the further round the player has to go.
+//!
+//! Grids are laid along the context's x, y and z axes, with cell `(0, 0, 0)`
+//! starting at the origin. Grids one cell deep ignore z.
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BinaryHeap},
+    sync::Mutex,
+};
+
+use crate::{
+    ffi::syz_Handle,
+    property,
+    scaled::{self, Layer},
+    Context, Handle, Object, Source3D, SynthizerError, WeakHandle,
+};
+
+type V = [f64; 3];
+
+fn distance(a: V, b: V) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+fn to_array((x, y, z): (f64, f64, f64)) -> V {
+    [x, y, z]
+}
+
+fn to_tuple([x, y, z]: V) -> (f64, f64, f64) {
+    (x, y, z)
+}
+
+/// Which cells of a 2D or 3D map sound passes through.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropagationGrid {
+    size: [usize; 3],
+    cell_size: f64,
+    passable: Vec<bool>,
+}
+
+/// How a sound reaches the listener.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Propagated {
+    /// How far the sound travels to the listener.
+    pub distance: f64,
+    /// The unit vector from the listener towards where the sound comes from.
+    pub direction: (f64, f64, f64),
+    /// Where the sound seems to be: `distance` along `direction` from the
+    /// listener.
+    pub position: (f64, f64, f64),
+}
+
+// An entry in Dijkstra's queue, ordered so the nearest cell pops first.
+#[derive(PartialEq)]
+struct Visit(f64, usize);
+
+impl Eq for Visit {}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0)
+    }
+}
+
+/// Path distances from one cell to every other.
+struct Field {
+    distances: Vec<f64>,
+    previous: Vec<usize>,
+}
+
+impl PropagationGrid {
+    /// A grid of `width` by `height` by `depth` cells, each `cell_size` units
+    /// wide, through which sound passes everywhere.
+    pub fn new(
+        width: usize,
+        height: usize,
+        depth: usize,
+        cell_size: f64,
+    ) -> Result<Self, SynthizerError> {
+        // Written so NaNs fail too.
+        let valid = width > 0 && height > 0 && depth > 0 && cell_size > 0. && cell_size.is_finite();
+        let cells = width.checked_mul(height).and_then(|v| v.checked_mul(depth));
+        match cells {
+            Some(cells) if valid => Ok(Self {
+                size: [width, height, depth],
+                cell_size,
+                passable: vec![true; cells],
+            }),
+            _ => Err(SynthizerError::InvalidArgument(format!(
+                "Can't make a {}x{}x{} grid of {} unit cells",
+                width, height, depth, cell_size
+            ))),
+        }
+    }
+
+    /// A 2D grid from rows of characters, the first row being y = 0, where
+    /// `wall` marks cells sound can't pass.
+    pub fn from_rows(rows: &[&str], wall: char, cell_size: f64) -> Result<Self, SynthizerError> {
+        let width = rows.iter().map(|v| v.chars().count()).max().unwrap_or(0);
+        let mut grid = Self::new(width, rows.len(), 1, cell_size)?;
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                grid.set_passable(x, y, 0, c != wall)?;
+            }
+        }
+        Ok(grid)
+    }
+
+    pub fn width(&self) -> usize {
+        self.size[0]
+    }
+
+    pub fn height(&self) -> usize {
+        self.size[1]
+    }
+
+    pub fn depth(&self) -> usize {
+        self.size[2]
+    }
+
+    pub fn cell_size(&self) -> f64 {
+        self.cell_size
+    }
+
+    fn index(&self, cell: [usize; 3]) -> Option<usize> {
+        let inside = (0..3).all(|i| cell[i] < self.size[i]);
+        if inside {
+            Some(cell[0] + self.size[0] * (cell[1] + self.size[1] * cell[2]))
+        } else {
+            None
+        }
+    }
+
+    fn cell(&self, index: usize) -> [usize; 3] {
+        let x = index % self.size[0];
+        let y = index / self.size[0] % self.size[1];
+        [x, y, index / (self.size[0] * self.size[1])]
+    }
+
+    fn center(&self, index: usize) -> V {
+        let cell = self.cell(index);
+        let mut center = [0.; 3];
+        for i in 0..3 {
+            center[i] = (cell[i] as f64 + 0.5) * self.cell_size;
+        }
+        if self.size[2] == 1 {
+            center[2] = 0.;
+        }
+        center
+    }
+
+    /// The cell containing `position`, if it's in the grid.
+    fn locate(&self, position: V) -> Option<usize> {
+        let mut cell = [0; 3];
+        for i in 0..3 {
+            if i == 2 && self.size[2] == 1 {
+                break;
+            }
+            let v = (position[i] / self.cell_size).floor();
+            let valid = v >= 0. && v < self.size[i] as f64;
+            if !valid {
+                return None;
+            }
+            cell[i] = v as usize;
+        }
+        self.index(cell)
+    }
+
+    /// Fails if the cell is outside the grid.
+    pub fn set_passable(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        passable: bool,
+    ) -> Result<(), SynthizerError> {
+        let index = self.index([x, y, z]).ok_or_else(|| {
+            SynthizerError::InvalidArgument(format!("({}, {}, {}) is outside the grid", x, y, z))
+        })?;
+        self.passable[index] = passable;
+        Ok(())
+    }
+
+    /// Whether sound passes through a cell, which it never does outside the
+    /// grid.
+    pub fn is_passable(&self, x: usize, y: usize, z: usize) -> bool {
+        self.index([x, y, z])
+            .map(|v| self.passable[v])
+            .unwrap_or(false)
+    }
+
+    /// Every cell a step from `index`, with the step's length. Diagonal steps
+    /// need every cell they brush past to be passable, so sound can't slip
+    /// between walls meeting at a corner.
+    fn neighbors(&self, index: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let cell = self.cell(index);
+        let depth = if self.size[2] == 1 { 0 } else { 1 };
+        let offsets = (-1..=1).flat_map(move |dz: isize| {
+            (-1..=1).flat_map(move |dy: isize| (-1..=1).map(move |dx: isize| [dx, dy, dz]))
+        });
+        offsets
+            .filter(move |offset| offset[2].abs() <= depth && *offset != [0, 0, 0])
+            .filter_map(move |offset| {
+                let target = self.offset(cell, offset)?;
+                // Every cell between here and there along some of the axes.
+                let brushed = (1..7).filter_map(|mask: usize| {
+                    let partial =
+                        [0, 1, 2].map(|i| if mask & (1 << i) != 0 { offset[i] } else { 0 });
+                    if partial == offset || partial == [0, 0, 0] {
+                        None
+                    } else {
+                        Some(partial)
+                    }
+                });
+                for partial in brushed {
+                    let passable = self
+                        .offset(cell, partial)
+                        .map(|v| self.passable[v])
+                        .unwrap_or(false);
+                    if !passable {
+                        return None;
+                    }
+                }
+                let steps = offset.iter().filter(|v| **v != 0).count() as f64;
+                Some((target, steps.sqrt() * self.cell_size))
+            })
+    }
+
+    fn offset(&self, cell: [usize; 3], offset: [isize; 3]) -> Option<usize> {
+        let mut target = [0; 3];
+        for i in 0..3 {
+            let v = cell[i] as isize + offset[i];
+            if v < 0 {
+                return None;
+            }
+            target[i] = v as usize;
+        }
+        self.index(target)
+    }
+
+    /// Shortest path distances from `start` between cell centers. Sound
+    /// spreads only through passable cells, but reaches walls next to them,
+    /// so sources inside walls can still be heard.
+    fn field(&self, start: usize) -> Field {
+        let mut distances = vec![f64::INFINITY; self.passable.len()];
+        let mut previous = vec![usize::MAX; self.passable.len()];
+        let mut queue = BinaryHeap::new();
+        distances[start] = 0.;
+        queue.push(Visit(0., start));
+        while let Some(Visit(d, index)) = queue.pop() {
+            if d > distances[index] || (index != start && !self.passable[index]) {
+                continue;
+            }
+            for (next, step) in self.neighbors(index) {
+                let d = d + step;
+                if d < distances[next] {
+                    distances[next] = d;
+                    previous[next] = index;
+                    queue.push(Visit(d, next));
+                }
+            }
+        }
+        Field {
+            distances,
+            previous,
+        }
+    }
+
+    /// Whether a straight line from `from` to `to` stays in passable cells,
+    /// not counting the cells it starts and ends in.
+    fn line_of_sight(&self, from: V, to: V) -> bool {
+        let (start, end) = (self.locate(from), self.locate(to));
+        let steps = (distance(from, to) / (self.cell_size / 4.)).ceil() as usize;
+        (1..steps).all(|i| {
+            let t = i as f64 / steps as f64;
+            let point = [0, 1, 2].map(|j| from[j] + (to[j] - from[j]) * t);
+            match self.locate(point) {
+                Some(cell) => Some(cell) == start || Some(cell) == end || self.passable[cell],
+                None => false,
+            }
+        })
+    }
+
+    fn propagate_in(&self, field: &Field, listener: V, source: V) -> Option<Propagated> {
+        let source_cell = self.locate(source)?;
+        if field.distances[source_cell].is_infinite() {
+            return None;
+        }
+        let straight = distance(listener, source);
+        if self.line_of_sight(listener, source) {
+            let direction = if straight > 0. {
+                [0, 1, 2].map(|i| (source[i] - listener[i]) / straight)
+            } else {
+                [0.; 3]
+            };
+            return Some(Propagated {
+                distance: straight,
+                direction: to_tuple(direction),
+                position: to_tuple(source),
+            });
+        }
+        // Walk back from the source to the first cell the listener can see:
+        // the corner the sound comes round.
+        let mut corner = source_cell;
+        while field.previous[corner] != usize::MAX {
+            corner = field.previous[corner];
+            if self.line_of_sight(listener, self.center(corner)) {
+                break;
+            }
+        }
+        let center = self.center(corner);
+        let to_corner = distance(listener, center);
+        let beyond = field.distances[source_cell] - field.distances[corner];
+        let travelled =
+            (to_corner + beyond + distance(self.center(source_cell), source)).max(straight);
+        let direction = if to_corner > 0. {
+            [0, 1, 2].map(|i| (center[i] - listener[i]) / to_corner)
+        } else {
+            [0.; 3]
+        };
+        Some(Propagated {
+            distance: travelled,
+            direction: to_tuple(direction),
+            position: to_tuple([0, 1, 2].map(|i| listener[i] + direction[i] * travelled)),
+        })
+    }
+
+    /// How sound from `source` reaches `listener`, or `None` if no path
+    /// connects them or either is outside the grid.
+    pub fn propagate(
+        &self,
+        listener: (f64, f64, f64),
+        source: (f64, f64, f64),
+    ) -> Option<Propagated> {
+        let listener = self.flatten(to_array(listener));
+        let field = self.field(self.locate(listener)?);
+        self.propagate_in(&field, listener, self.flatten(to_array(source)))
+    }
+
+    /// Drops z from positions in grids one cell deep.
+    fn flatten(&self, mut position: V) -> V {
+        if self.size[2] == 1 {
+            position[2] = 0.;
+        }
+        position
+    }
+}
+
+/// How [`Propagation`] treats sources.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PropagationConfig {
+    /// What the gain of sources no path reaches is multiplied by.
+    pub unreachable_gain: f64,
+    /// Sources further along their path than this are unreachable.
+    pub max_distance: f64,
+    /// Seconds over which sources glide to new positions and gains.
+    pub fade_time: f64,
+}
+
+impl Default for PropagationConfig {
+    fn default() -> Self {
+        Self {
+            unreachable_gain: 0.,
+            max_distance: f64::INFINITY,
+            fade_time: 0.1,
+        }
+    }
+}
+
+impl PropagationConfig {
+    fn validate(&self) -> Result<(), SynthizerError> {
+        // Written so NaNs fail too.
+        let valid = self.unreachable_gain >= 0.
+            && self.unreachable_gain <= 1.
+            && self.max_distance > 0.
+            && self.fade_time >= 0.
+            && self.fade_time.is_finite();
+        if valid {
+            Ok(())
+        } else {
+            Err(SynthizerError::InvalidArgument(format!(
+                "Invalid propagation configuration {:?}",
+                self
+            )))
+        }
+    }
+}
+
+// Where each propagated source really is, for occlusion. Entries for dropped
+// sources are pruned whenever one is added.
+static REAL_POSITIONS: Mutex<BTreeMap<syz_Handle, (WeakHandle, V)>> = Mutex::new(BTreeMap::new());
+
+/// Where `source` really is, if a [`Propagation`] is moving it.
+pub(crate) fn real_position(source: &Handle) -> Option<(f64, f64, f64)> {
+    let positions = REAL_POSITIONS.lock().unwrap();
+    positions
+        .get(&**source)
+        .filter(|v| v.0.is(source))
+        .map(|v| to_tuple(v.1))
+}
+
+fn set_real_position(source: &Handle, position: V) {
+    let mut positions = REAL_POSITIONS.lock().unwrap();
+    if !positions.get(&**source).is_some_and(|v| v.0.is(source)) {
+        positions.retain(|_, v| v.0.upgrade().is_some());
+    }
+    positions.insert(**source, (source.downgrade(), position));
+}
+
+fn clear_real_position(source: &Handle) {
+    let mut positions = REAL_POSITIONS.lock().unwrap();
+    if positions.get(&**source).is_some_and(|v| v.0.is(source)) {
+        positions.remove(&**source);
+    }
+}
+
+#[derive(Debug)]
+struct Tracked {
+    source: WeakHandle,
+    // Where the source really is, in the context's coordinates.
+    position: V,
+    propagated: Option<Propagated>,
+    // The position, in the context's coordinates, and gain factor last
+    // applied.
+    applied: Option<(V, f64)>,
+}
+
+/// Moves sources to where a [`PropagationGrid`] says they're heard from.
+///
+/// Created by [`Context::new_propagation`]. Each source's real position is
+/// given here rather than to the source, and [`update`](Propagation::update)
+/// moves the source to where it's heard from, scaling the gain the
+/// application sets to silence sources no path reaches.
+///
+/// Sources and the context aren't kept alive by this, and sources are
+/// forgotten once dropped.
+#[derive(Debug)]
+pub struct Propagation {
+    context: WeakHandle,
+    grid: PropagationGrid,
+    config: PropagationConfig,
+    sources: Vec<Tracked>,
+}
+
+impl Propagation {
+    pub fn grid(&self) -> &PropagationGrid {
+        &self.grid
+    }
+
+    /// Changes take effect at the next update.
+    pub fn grid_mut(&mut self) -> &mut PropagationGrid {
+        &mut self.grid
+    }
+
+    pub fn config(&self) -> PropagationConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: PropagationConfig) -> Result<(), SynthizerError> {
+        config.validate()?;
+        self.config = config;
+        Ok(())
+    }
+
+    fn context(&self) -> Result<Context, SynthizerError> {
+        self.context
+            .upgrade()
+            .map(Context)
+            .ok_or_else(|| SynthizerError::InvalidArgument("Context was dropped".to_string()))
+    }
+
+    fn find(&self, source: &Source3D) -> Option<usize> {
+        self.sources
+            .iter()
+            .position(|v| v.source.is(source.handle()))
+    }
+
+    /// Sets where `source` really is, starting to propagate it if it wasn't
+    /// already.
+    pub fn set_source_position(
+        &mut self,
+        source: &Source3D,
+        x: f64,
+        y: f64,
+        z: f64,
+    ) -> Result<(), SynthizerError> {
+        set_real_position(source.handle(), [x, y, z]);
+        match self.find(source) {
+            Some(index) => self.sources[index].position = [x, y, z],
+            None => self.sources.push(Tracked {
+                source: source.handle().downgrade(),
+                position: [x, y, z],
+                propagated: None,
+                applied: None,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Stops propagating `source`, putting it back at its real position and
+    /// no longer scaling its gain.
+    pub fn remove_source(&mut self, source: &Source3D) -> Result<(), SynthizerError> {
+        if let Some(index) = self.find(source) {
+            let tracked = self.sources.remove(index);
+            clear_real_position(source.handle());
+            let mut batch = self.context()?.new_automation_batch()?;
+            batch.clear(source, property::POSITION)?;
+            batch.execute()?;
+            let [x, y, z] = tracked.position;
+            source.set_position(x, y, z)?;
+            scaled::remove_factor(source.handle(), property::GAIN, Layer::Propagation)?;
+        }
+        Ok(())
+    }
+
+    /// How `source` was heard at the last update, or `None` if it wasn't
+    /// reachable or hasn't been updated.
+    pub fn get_propagated(&self, source: &Source3D) -> Option<Propagated> {
+        self.find(source).and_then(|v| self.sources[v].propagated)
+    }
+
+    /// Moves every source to where it's heard from the listener's current
+    /// position.
+    pub fn update(&mut self) -> Result<(), SynthizerError> {
+        let context = self.context()?;
+        self.sources.retain(|v| v.source.upgrade().is_some());
+        let listener = self.grid.flatten(to_array(context.get_position()?));
+        let field = self.grid.locate(listener).map(|v| self.grid.field(v));
+        let start = context.get_suggested_automation_time()?;
+        let end = start + self.config.fade_time;
+        let mut batch = context.new_automation_batch()?;
+        let mut scheduled = false;
+        let (grid, config) = (&self.grid, self.config);
+        for tracked in self.sources.iter_mut() {
+            let source = match tracked.source.upgrade() {
+                Some(v) => Source3D(v),
+                None => continue,
+            };
+            let position = grid.flatten(tracked.position);
+            let propagated = field
+                .as_ref()
+                .and_then(|v| grid.propagate_in(v, listener, position))
+                .filter(|v| v.distance <= config.max_distance);
+            // Unreachable sources stay put, so they don't seem to jump when
+            // they come back.
+            let (mut heard_at, gain) = match &propagated {
+                Some(v) => (to_array(v.position), 1.),
+                None => {
+                    let last = tracked.propagated.map(|v| to_array(v.position));
+                    (last.unwrap_or(position), config.unreachable_gain)
+                }
+            };
+            // Flat grids leave sources at their own height.
+            if grid.size[2] == 1 {
+                heard_at[2] = tracked.position[2];
+            }
+            let (from, from_gain) = tracked.applied.unwrap_or((tracked.position, 1.));
+            if tracked.applied.is_none() || from != heard_at {
+                batch
+                    .clear(&source, property::POSITION)?
+                    .set(&source, property::POSITION, start, to_tuple(from))?
+                    .linear_ramp_to(&source, property::POSITION, end, to_tuple(heard_at))?;
+                scheduled = true;
+            }
+            if from_gain != gain {
+                let layer = Layer::Propagation;
+                scaled::set_factor(
+                    source.handle(),
+                    property::GAIN,
+                    layer,
+                    gain,
+                    config.fade_time,
+                )?;
+            }
+            tracked.applied = Some((heard_at, gain));
+            tracked.propagated = propagated;
+        }
+        if scheduled {
+            batch.execute()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Propagation {
+    fn drop(&mut self) {
+        for source in self.sources.iter().filter_map(|v| v.source.upgrade()) {
+            clear_real_position(&source);
+        }
+    }
+}
+
+impl Context {
+    pub fn new_propagation(
+        &self,
+        grid: PropagationGrid,
+        config: PropagationConfig,
+    ) -> Result<Propagation, SynthizerError> {
+        config.validate()?;
+        Ok(Propagation {
+            context: self.handle().downgrade(),
+            grid,
+            config,
+            sources: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mock, Source, Synthizer};
+
+    // A wall along y = 2 with a doorway at its east end.
+    const ROOMS: [&str; 5] = [".....", ".....", "####.", ".....", "....."];
+
+    fn close(a: (f64, f64, f64), b: (f64, f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9 && (a.2 - b.2).abs() < 1e-9
+    }
+
+    #[test]
+    fn hears_sources_in_sight_directly() -> Result<(), SynthizerError> {
+        let grid = PropagationGrid::from_rows(&ROOMS, '#', 1.)?;
+        let heard = grid.propagate((0.5, 0.5, 0.), (3.5, 0.5, 0.)).unwrap();
+        assert_eq!(heard.distance, 3.);
+        assert!(close(heard.direction, (1., 0., 0.)));
+        assert!(close(heard.position, (3.5, 0.5, 0.)));
+        Ok(())
+    }
+
+    #[test]
+    fn leads_through_doorways() -> Result<(), SynthizerError> {
+        let grid = PropagationGrid::from_rows(&ROOMS, '#', 1.)?;
+        let heard = grid.propagate((0.5, 0.5, 0.), (0.5, 4.5, 0.)).unwrap();
+        // Round through the doorway at x = 4, not 4 units straight through
+        // the wall.
+        assert!(heard.distance > 8.);
+        assert!(heard.direction.0 > heard.direction.1.abs());
+        let (x, y, _) = heard.position;
+        let travelled = (x - 0.5).hypot(y - 0.5);
+        assert!((travelled - heard.distance).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn blocks_corners_and_closed_rooms() -> Result<(), SynthizerError> {
+        // Walls meeting at a corner leave no gap.
+        let grid = PropagationGrid::from_rows(&[".#", "#."], '#', 1.)?;
+        assert!(grid.propagate((0.5, 0.5, 0.), (1.5, 1.5, 0.)).is_none());
+        let mut grid = PropagationGrid::from_rows(&ROOMS, '#', 1.)?;
+        grid.set_passable(4, 2, 0, false)?;
+        assert!(!grid.is_passable(4, 2, 0));
+        assert!(grid.propagate((0.5, 0.5, 0.), (0.5, 4.5, 0.)).is_none());
+        // Sources inside walls are still heard from next to them.
+        assert!(grid.propagate((0.5, 0.5, 0.), (0.5, 2.5, 0.)).is_some());
+        assert!(grid.propagate((0.5, 0.5, 0.), (9., 9., 0.)).is_none());
+        assert!(grid.set_passable(5, 0, 0, false).is_err());
+        assert!(PropagationGrid::new(0, 1, 1, 1.).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn propagates_between_floors() -> Result<(), SynthizerError> {
+        // Two floors joined by a stairwell at (2, 2).
+        let mut grid = PropagationGrid::new(3, 3, 3, 2.)?;
+        for x in 0..3 {
+            for y in 0..3 {
+                if (x, y) != (2, 2) {
+                    grid.set_passable(x, y, 1, false)?;
+                }
+            }
+        }
+        let heard = grid.propagate((1., 1., 1.), (1., 1., 5.)).unwrap();
+        assert!(heard.distance > 4.);
+        assert!(heard.direction.0 > 0. && heard.direction.1 > 0.);
+        Ok(())
+    }
+
+    #[test]
+    fn moves_sources() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        source.set_gain(0.5)?;
+        let grid = PropagationGrid::from_rows(&ROOMS, '#', 1.)?;
+        let mut propagation = context.new_propagation(grid, PropagationConfig::default())?;
+        context.set_position(0.5, 0.5, 0.)?;
+        propagation.set_source_position(&source, 0.5, 4.5, 0.)?;
+        propagation.update()?;
+        let heard = propagation.get_propagated(&source).unwrap();
+        let points = mock::automation(**source.handle(), property::POSITION.id());
+        assert_eq!(points[0].2[..3], [0.5, 4.5, 0.]);
+        let (x, y, z) = heard.position;
+        assert_eq!(points[1].2[..3], [x, y, z]);
+        assert!(mock::automation(**source.handle(), property::GAIN.id()).is_empty());
+        propagation.grid_mut().set_passable(4, 2, 0, false)?;
+        propagation.update()?;
+        assert!(propagation.get_propagated(&source).is_none());
+        let gains = mock::automation(**source.handle(), property::GAIN.id());
+        assert_eq!((gains[0].2[0], gains[1].2[0]), (0.5, 0.));
+        propagation.remove_source(&source)?;
+        assert!(mock::automation(**source.handle(), property::POSITION.id()).is_empty());
+        assert_eq!(source.get_position()?, (0.5, 4.5, 0.));
+        assert_eq!(source.get_gain()?, 0.5);
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_gain_set_while_unreachable() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        let grid = PropagationGrid::from_rows(&ROOMS, '#', 1.)?;
+        let mut propagation = context.new_propagation(grid, PropagationConfig::default())?;
+        context.set_position(0.5, 0.5, 0.)?;
+        propagation.set_source_position(&source, 0.5, 4.5, 0.)?;
+        propagation.grid_mut().set_passable(4, 2, 0, false)?;
+        propagation.update()?;
+        source.set_gain(0.25)?;
+        assert_eq!(source.get_gain()?, 0.25);
+        let gains = mock::automation(**source.handle(), property::GAIN.id());
+        assert_eq!(gains.last().unwrap().2[0], 0.);
+        propagation.remove_source(&source)?;
+        assert_eq!(source.get_gain()?, 0.25);
+        Ok(())
+    }
+
+    #[test]
+    fn forgets_dropped_sources() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        let raw = **source.handle();
+        let grid = PropagationGrid::from_rows(&ROOMS, '#', 1.)?;
+        let mut propagation = context.new_propagation(grid, PropagationConfig::default())?;
+        propagation.set_source_position(&source, 0.5, 4.5, 0.)?;
+        drop(source);
+        assert_eq!(mock::refcount(raw), None);
+        propagation.update()?;
+        assert!(propagation.sources.is_empty());
+        Ok(())
+    }
+}
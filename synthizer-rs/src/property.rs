@@ -0,0 +1,519 @@
+//! Typed property keys.
+//!
+//! Every Synthizer object exposes its state as properties. A `Property<T>`
+//! names one of them along with the Rust type its value converts to, so the
+//! same key can be read or written on any object through `Object::get` and
+//! `Object::set`.
+
+use std::{fmt, marker::PhantomData};
+
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crate::{
+    ffi::*,
+    occlusion,
+    orientation::{values_from_synthizer, values_to_synthizer},
+    scaled, Buffer, DistanceModel, Handle, NoiseType, PannerStrategy, SynthizerError,
+};
+
+pub struct Property<T> {
+    id: i32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Property<T> {
+    pub(crate) const fn new(id: i32) -> Self {
+        Self {
+            id,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl<T> Clone for Property<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Property<T> {}
+
+impl<T> fmt::Debug for Property<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Property").field(&self.id).finish()
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A type which can be stored in a Synthizer property.
+pub trait PropertyValue: private::Sealed + Sized {
+    fn get(handle: &Handle, property: i32) -> Result<Self, SynthizerError>;
+
+    fn set(self, handle: &Handle, property: i32) -> Result<(), SynthizerError>;
+}
+
+impl private::Sealed for i32 {}
+
+impl PropertyValue for i32 {
+    fn get(handle: &Handle, property: i32) -> Result<Self, SynthizerError> {
+        let mut out = 0;
+        wrap!(unsafe { syz_getI(&mut out, **handle, property) }, out)
+    }
+
+    fn set(self, handle: &Handle, property: i32) -> Result<(), SynthizerError> {
+        wrap!(unsafe { syz_setI(**handle, property, self) })
+    }
+}
+
+impl private::Sealed for bool {}
+
+impl PropertyValue for bool {
+    fn get(handle: &Handle, property: i32) -> Result<Self, SynthizerError> {
+        let v = i32::get(handle, property)?;
+        Ok(v != 0)
+    }
+
+    fn set(self, handle: &Handle, property: i32) -> Result<(), SynthizerError> {
+        let v = if self { 1 } else { 0 };
+        v.set(handle, property)
+    }
+}
+
+// Enumerated properties are stored as ints.
+macro_rules! enum_values {
+    ($($type:ident: $name:literal,)*) => {
+        $(
+            impl private::Sealed for $type {}
+
+            impl PropertyValue for $type {
+                fn get(handle: &Handle, property: i32) -> Result<Self, SynthizerError> {
+                    let v = i32::get(handle, property)?;
+                    $type::from_i32(v).ok_or_else(|| {
+                        SynthizerError::InvalidArgument(format!("Unknown {} {}", $name, v))
+                    })
+                }
+
+                fn set(self, handle: &Handle, property: i32) -> Result<(), SynthizerError> {
+                    let v = self.to_i32().expect("Enums are represented as i32");
+                    v.set(handle, property)
+                }
+            }
+        )*
+    };
+}
+
+enum_values! {
+    DistanceModel: "distance model",
+    NoiseType: "noise type",
+    PannerStrategy: "panner strategy",
+}
+
+impl private::Sealed for f64 {}
+
+// Properties this crate's effects also change hold the application's value
+// separately, so it reads back as set.
+impl PropertyValue for f64 {
+    fn get(handle: &Handle, property: i32) -> Result<Self, SynthizerError> {
+        if let Some(v) = scaled::get(handle, property)? {
+            return Ok(v);
+        }
+        let mut out = 0.;
+        wrap!(unsafe { syz_getD(&mut out, **handle, property) }, out)
+    }
+
+    fn set(self, handle: &Handle, property: i32) -> Result<(), SynthizerError> {
+        if scaled::set(handle, property, self)? {
+            return Ok(());
+        }
+        wrap!(unsafe { syz_setD(**handle, property, self) })
+    }
+}
+
+impl private::Sealed for (f64, f64, f64) {}
+
+// Positions and orientations are converted between the context's coordinates
+// and Synthizer's here, so every way of setting them agrees.
+impl PropertyValue for (f64, f64, f64) {
+    fn get(handle: &Handle, property: i32) -> Result<Self, SynthizerError> {
+        let (mut x, mut y, mut z) = (0., 0., 0.);
+        wrap!(unsafe { syz_getD3(&mut x, &mut y, &mut z, **handle, property) })?;
+        let [x, y, z, ..] = values_from_synthizer(handle, property, [x, y, z, 0., 0., 0.]);
+        Ok((x, y, z))
+    }
+
+    fn set(self, handle: &Handle, property: i32) -> Result<(), SynthizerError> {
+        let [x, y, z, ..] = values_to_synthizer(handle, property, self.to_values());
+        wrap!(unsafe { syz_setD3(**handle, property, x, y, z) })
+    }
+}
+
+impl private::Sealed for (f64, f64, f64, f64, f64, f64) {}
+
+impl PropertyValue for (f64, f64, f64, f64, f64, f64) {
+    fn get(handle: &Handle, property: i32) -> Result<Self, SynthizerError> {
+        let (mut x1, mut y1, mut z1) = (0., 0., 0.);
+        let (mut x2, mut y2, mut z2) = (0., 0., 0.);
+        wrap!(unsafe {
+            syz_getD6(
+                &mut x1, &mut y1, &mut z1, &mut x2, &mut y2, &mut z2, **handle, property,
+            )
+        })?;
+        let [x1, y1, z1, x2, y2, z2] =
+            values_from_synthizer(handle, property, [x1, y1, z1, x2, y2, z2]);
+        Ok((x1, y1, z1, x2, y2, z2))
+    }
+
+    fn set(self, handle: &Handle, property: i32) -> Result<(), SynthizerError> {
+        let [x1, y1, z1, x2, y2, z2] = values_to_synthizer(handle, property, self.to_values());
+        wrap!(unsafe { syz_setD6(**handle, property, x1, y1, z1, x2, y2, z2) })
+    }
+}
+
+/// The coefficients of a biquad filter.
+///
+/// Build one with a designer such as [`BiquadConfig::lowpass`], then set it on
+/// a source or effect. Frequencies are in Hz, and must be below half of
+/// [`SAMPLE_RATE`](crate::SAMPLE_RATE).
+#[derive(Clone, Copy, Debug)]
+pub struct BiquadConfig(pub(crate) syz_BiquadConfig);
+
+impl BiquadConfig {
+    /// The Q giving the flattest passband, and a good default.
+    pub const DEFAULT_Q: f64 = std::f64::consts::FRAC_1_SQRT_2;
+
+    fn design(
+        frequency: f64,
+        width: f64,
+        f: impl FnOnce(&mut syz_BiquadConfig) -> syz_ErrorCode,
+    ) -> Result<Self, SynthizerError> {
+        let nyquist = crate::SAMPLE_RATE as f64 / 2.;
+        if !(frequency > 0. && frequency < nyquist) {
+            return Err(SynthizerError::InvalidArgument(format!(
+                "Filter frequency of {} Hz isn't between 0 and {}",
+                frequency, nyquist
+            )));
+        }
+        if !(width > 0. && width.is_finite()) {
+            return Err(SynthizerError::InvalidArgument(format!(
+                "Filter Q or bandwidth must be positive, not {}",
+                width
+            )));
+        }
+        let mut out = unsafe { std::mem::zeroed::<syz_BiquadConfig>() };
+        wrap!(f(&mut out), Self(out))
+    }
+
+    /// A filter which passes everything through unchanged.
+    pub fn identity() -> Result<Self, SynthizerError> {
+        let mut out = unsafe { std::mem::zeroed::<syz_BiquadConfig>() };
+        wrap!(unsafe { syz_biquadDesignIdentity(&mut out) }, Self(out))
+    }
+
+    /// Passes frequencies below `frequency`, for sounds heard through walls
+    /// or underwater.
+    pub fn lowpass(frequency: f64, q: f64) -> Result<Self, SynthizerError> {
+        Self::design(frequency, q, |out| unsafe {
+            syz_biquadDesignLowpass(out, frequency, q)
+        })
+    }
+
+    /// Passes frequencies above `frequency`, for tinny sounds such as radios.
+    pub fn highpass(frequency: f64, q: f64) -> Result<Self, SynthizerError> {
+        Self::design(frequency, q, |out| unsafe {
+            syz_biquadDesignHighpass(out, frequency, q)
+        })
+    }
+
+    /// Passes frequencies around `frequency`, `bandwidth` octaves wide.
+    pub fn bandpass(frequency: f64, bandwidth: f64) -> Result<Self, SynthizerError> {
+        Self::design(frequency, bandwidth, |out| unsafe {
+            syz_biquadDesignBandpass(out, frequency, bandwidth)
+        })
+    }
+}
+
+impl PartialEq for BiquadConfig {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (&self.0, &other.0);
+        a._b0 == b._b0
+            && a._b1 == b._b1
+            && a._b2 == b._b2
+            && a._a1 == b._a1
+            && a._a2 == b._a2
+            && a._gain == b._gain
+            && a._is_wire == b._is_wire
+    }
+}
+
+impl private::Sealed for BiquadConfig {}
+
+// Occlusion replaces the direct path filter while holding on to the
+// application's, so that reads back as set too.
+impl PropertyValue for BiquadConfig {
+    fn get(handle: &Handle, property: i32) -> Result<Self, SynthizerError> {
+        if let Some(v) = occlusion::get_filter(handle, property) {
+            return Ok(v);
+        }
+        let mut out = unsafe { std::mem::zeroed::<syz_BiquadConfig>() };
+        wrap!(
+            unsafe { syz_getBiquad(&mut out, **handle, property) },
+            BiquadConfig(out)
+        )
+    }
+
+    fn set(self, handle: &Handle, property: i32) -> Result<(), SynthizerError> {
+        if occlusion::set_filter(handle, property, self) {
+            return Ok(());
+        }
+        wrap!(unsafe { syz_setBiquad(**handle, property, &self.0) })
+    }
+}
+
+/// A property type Synthizer can automate.
+pub trait Automatable: PropertyValue {
+    #[doc(hidden)]
+    fn to_values(self) -> [f64; 6];
+}
+
+impl Automatable for f64 {
+    fn to_values(self) -> [f64; 6] {
+        [self, 0., 0., 0., 0., 0.]
+    }
+}
+
+impl Automatable for (f64, f64, f64) {
+    fn to_values(self) -> [f64; 6] {
+        let (x, y, z) = self;
+        [x, y, z, 0., 0., 0.]
+    }
+}
+
+impl Automatable for (f64, f64, f64, f64, f64, f64) {
+    fn to_values(self) -> [f64; 6] {
+        let (x1, y1, z1, x2, y2, z2) = self;
+        [x1, y1, z1, x2, y2, z2]
+    }
+}
+
+impl private::Sealed for Option<Buffer> {}
+
+impl PropertyValue for Option<Buffer> {
+    fn get(handle: &Handle, property: i32) -> Result<Self, SynthizerError> {
+        Ok(handle.get_object(property).map(Buffer))
+    }
+
+    fn set(self, handle: &Handle, property: i32) -> Result<(), SynthizerError> {
+        handle.set_object(property, self.map(|v| v.0))
+    }
+}
+
+macro_rules! properties {
+    ($($name:ident: $type:ty = $constant:ident,)*) => {
+        $(
+            pub const $name: Property<$type> = Property::new($constant as i32);
+        )*
+    };
+}
+
+properties! {
+    AZIMUTH: f64 = SYZ_PROPERTIES_SYZ_P_AZIMUTH,
+    BUFFER: Option<Buffer> = SYZ_PROPERTIES_SYZ_P_BUFFER,
+    ELEVATION: f64 = SYZ_PROPERTIES_SYZ_P_ELEVATION,
+    GAIN: f64 = SYZ_PROPERTIES_SYZ_P_GAIN,
+    DEFAULT_PANNER_STRATEGY: PannerStrategy = SYZ_PROPERTIES_SYZ_P_DEFAULT_PANNER_STRATEGY,
+    PANNING_SCALAR: f64 = SYZ_PROPERTIES_SYZ_P_PANNING_SCALAR,
+    PLAYBACK_POSITION: f64 = SYZ_PROPERTIES_SYZ_P_PLAYBACK_POSITION,
+    POSITION: (f64, f64, f64) = SYZ_PROPERTIES_SYZ_P_POSITION,
+    ORIENTATION: (f64, f64, f64, f64, f64, f64) = SYZ_PROPERTIES_SYZ_P_ORIENTATION,
+    CLOSENESS_BOOST: f64 = SYZ_PROPERTIES_SYZ_P_CLOSENESS_BOOST,
+    CLOSENESS_BOOST_DISTANCE: f64 = SYZ_PROPERTIES_SYZ_P_CLOSENESS_BOOST_DISTANCE,
+    DISTANCE_MAX: f64 = SYZ_PROPERTIES_SYZ_P_DISTANCE_MAX,
+    DISTANCE_MODEL: DistanceModel = SYZ_PROPERTIES_SYZ_P_DISTANCE_MODEL,
+    DISTANCE_REF: f64 = SYZ_PROPERTIES_SYZ_P_DISTANCE_REF,
+    ROLLOFF: f64 = SYZ_PROPERTIES_SYZ_P_ROLLOFF,
+    DEFAULT_CLOSENESS_BOOST: f64 = SYZ_PROPERTIES_SYZ_P_DEFAULT_CLOSENESS_BOOST,
+    DEFAULT_CLOSENESS_BOOST_DISTANCE: f64 = SYZ_PROPERTIES_SYZ_P_DEFAULT_CLOSENESS_BOOST_DISTANCE,
+    DEFAULT_DISTANCE_MAX: f64 = SYZ_PROPERTIES_SYZ_P_DEFAULT_DISTANCE_MAX,
+    DEFAULT_DISTANCE_MODEL: DistanceModel = SYZ_PROPERTIES_SYZ_P_DEFAULT_DISTANCE_MODEL,
+    DEFAULT_DISTANCE_REF: f64 = SYZ_PROPERTIES_SYZ_P_DEFAULT_DISTANCE_REF,
+    DEFAULT_ROLLOFF: f64 = SYZ_PROPERTIES_SYZ_P_DEFAULT_ROLLOFF,
+    LOOPING: bool = SYZ_PROPERTIES_SYZ_P_LOOPING,
+    NOISE_TYPE: NoiseType = SYZ_PROPERTIES_SYZ_P_NOISE_TYPE,
+    PITCH_BEND: f64 = SYZ_PROPERTIES_SYZ_P_PITCH_BEND,
+    MEAN_FREE_PATH: f64 = SYZ_PROPERTIES_SYZ_P_MEAN_FREE_PATH,
+    T60: f64 = SYZ_PROPERTIES_SYZ_P_T60,
+    LATE_REFLECTIONS_LF_ROLLOFF: f64 = SYZ_PROPERTIES_SYZ_P_LATE_REFLECTIONS_LF_ROLLOFF,
+    LATE_REFLECTIONS_LF_REFERENCE: f64 = SYZ_PROPERTIES_SYZ_P_LATE_REFLECTIONS_LF_REFERENCE,
+    LATE_REFLECTIONS_HF_ROLLOFF: f64 = SYZ_PROPERTIES_SYZ_P_LATE_REFLECTIONS_HF_ROLLOFF,
+    LATE_REFLECTIONS_HF_REFERENCE: f64 = SYZ_PROPERTIES_SYZ_P_LATE_REFLECTIONS_HF_REFERENCE,
+    LATE_REFLECTIONS_DIFFUSION: f64 = SYZ_PROPERTIES_SYZ_P_LATE_REFLECTIONS_DIFFUSION,
+    LATE_REFLECTIONS_MODULATION_DEPTH: f64 = SYZ_PROPERTIES_SYZ_P_LATE_REFLECTIONS_MODULATION_DEPTH,
+    LATE_REFLECTIONS_MODULATION_FREQUENCY: f64 =
+        SYZ_PROPERTIES_SYZ_P_LATE_REFLECTIONS_MODULATION_FREQUENCY,
+    LATE_REFLECTIONS_DELAY: f64 = SYZ_PROPERTIES_SYZ_P_LATE_REFLECTIONS_DELAY,
+    FILTER: BiquadConfig = SYZ_PROPERTIES_SYZ_P_FILTER,
+    FILTER_DIRECT: BiquadConfig = SYZ_PROPERTIES_SYZ_P_FILTER_DIRECT,
+    FILTER_EFFECTS: BiquadConfig = SYZ_PROPERTIES_SYZ_P_FILTER_EFFECTS,
+    FILTER_INPUT: BiquadConfig = SYZ_PROPERTIES_SYZ_P_FILTER_INPUT,
+    FREQUENCY: f64 = SYZ_PROPERTIES_SYZ_P_FREQUENCY,
+    CURRENT_TIME: f64 = SYZ_PROPERTIES_SYZ_P_CURRENT_TIME,
+    SUGGESTED_AUTOMATION_TIME: f64 = SYZ_PROPERTIES_SYZ_P_SUGGESTED_AUTOMATION_TIME,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Object, Source, Synthizer};
+
+    #[test]
+    fn enum_round_trip() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let generator = context.new_noise_generator(2)?;
+        generator.set_noise_type(NoiseType::FilteredBrown)?;
+        assert_eq!(generator.get_noise_type()?, NoiseType::FilteredBrown);
+        let mut raw = 0;
+        wrap!(unsafe { syz_getI(&mut raw, **generator.handle(), NOISE_TYPE.id()) })?;
+        assert_eq!(raw, SYZ_NOISE_TYPE_SYZ_NOISE_TYPE_FILTERED_BROWN as i32);
+        context.set_default_distance_model(DistanceModel::Inverse)?;
+        assert_eq!(
+            context.get_default_distance_model()?,
+            DistanceModel::Inverse
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unknown_enum_values() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        wrap!(unsafe { syz_setI(**source.handle(), DISTANCE_MODEL.id(), 100) })?;
+        let error = source.get_distance_model().unwrap_err();
+        assert!(matches!(error, SynthizerError::InvalidArgument(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn bool_round_trip() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let generator = context.new_buffer_generator()?;
+        generator.set_looping(true)?;
+        assert!(generator.get_looping()?);
+        generator.set_looping(false)?;
+        assert!(!generator.get_looping()?);
+        Ok(())
+    }
+
+    #[test]
+    fn double_round_trip() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_direct_source()?;
+        source.set_gain(0.25)?;
+        assert_eq!(source.get_gain()?, 0.25);
+        Ok(())
+    }
+
+    #[test]
+    fn vec3_round_trip() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        source.set_position(1., 2., 3.)?;
+        assert_eq!(source.get_position()?, (1., 2., 3.));
+        Ok(())
+    }
+
+    #[test]
+    fn orientation_round_trip() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let context = synthizer.new_context()?;
+        context.set_orientation(1., 0., 0., 0., 0., 1.)?;
+        assert_eq!(context.get_orientation()?, (1., 0., 0., 0., 0., 1.));
+        Ok(())
+    }
+
+    #[test]
+    fn biquad_round_trip() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_direct_source()?;
+        let mut config = source.get(FILTER)?;
+        config.0._b0 = 0.5;
+        config.0._a1 = -0.25;
+        source.set(FILTER, config)?;
+        let config = source.get(FILTER)?;
+        assert_eq!(config.0._b0, 0.5);
+        assert_eq!(config.0._a1, -0.25);
+        Ok(())
+    }
+
+    // The gain of a filter at DC, where z = 1.
+    fn dc_gain(config: &BiquadConfig) -> f64 {
+        let c = &config.0;
+        c._gain * (c._b0 + c._b1 + c._b2) / (1. + c._a1 + c._a2)
+    }
+
+    #[test]
+    fn designs_filters() -> Result<(), SynthizerError> {
+        let identity = BiquadConfig::identity()?;
+        assert_eq!(identity.0._is_wire, 1);
+        assert_eq!(dc_gain(&identity), 1.);
+        let lowpass = BiquadConfig::lowpass(1000., BiquadConfig::DEFAULT_Q)?;
+        assert!((dc_gain(&lowpass) - 1.).abs() < 1e-9);
+        let highpass = BiquadConfig::highpass(1000., BiquadConfig::DEFAULT_Q)?;
+        assert!(dc_gain(&highpass).abs() < 1e-9);
+        let bandpass = BiquadConfig::bandpass(1000., 1.)?;
+        assert!(dc_gain(&bandpass).abs() < 1e-9);
+        assert_ne!(
+            lowpass,
+            BiquadConfig::lowpass(2000., BiquadConfig::DEFAULT_Q)?
+        );
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_source3d()?;
+        source.set_filter_direct(lowpass)?;
+        source.set_filter_effects(highpass)?;
+        assert_eq!(source.get_filter_direct()?, lowpass);
+        assert_eq!(source.get_filter_effects()?, highpass);
+        for (frequency, q) in [(0., 1.), (30000., 1.), (f64::NAN, 1.), (1000., 0.)].iter() {
+            let error = BiquadConfig::lowpass(*frequency, *q).unwrap_err();
+            assert!(matches!(error, SynthizerError::InvalidArgument(_)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn object_round_trip() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let generator = context.new_buffer_generator()?;
+        assert!(generator.get_buffer()?.is_none());
+        let buffer = crate::Buffer::new(crate::Protocol::File, "test.wav".as_ref(), "")?;
+        let raw = **buffer.handle();
+        generator.set_buffer(buffer)?;
+        let buffer = generator.get_buffer()?.unwrap();
+        assert_eq!(**buffer.handle(), raw);
+        Ok(())
+    }
+
+    #[test]
+    fn generic_access_matches_accessors() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let context = synthizer.new_context()?;
+        context.set(POSITION, (4., 5., 6.))?;
+        assert_eq!(context.get_position()?, (4., 5., 6.));
+        context.set_default_rolloff(2.)?;
+        assert_eq!(context.get(DEFAULT_ROLLOFF)?, 2.);
+        Ok(())
+    }
+}
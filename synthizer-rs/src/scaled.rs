@@ -0,0 +1,394 @@
+//! Properties which both the application and this crate's effects change.
+//!
+//! Fades ramp gain while occlusion and propagation lower it, Doppler bends
+//! pitch, and the application keeps setting both. Rather than each
+//! overwriting the others, such a property's value is the application's,
+//! multiplied by a factor for each effect. The product is set directly when
+//! nothing is moving, and rescheduled with automation while anything ramps.
+//! Reading the property gives back the application's value.
+//!
+//! A property is only tracked while an effect scales it or a fade is under
+//! way. Once only the application's value is left, it goes back to being
+//! set directly.
+
+use std::{collections::BTreeMap, iter::once, sync::Mutex};
+
+use crate::{automation::Target, ffi::*, Context, Handle, Property, SynthizerError, WeakHandle};
+
+/// A value moving in a straight line from `from` at `start` to `to` at
+/// `end`, on the context's clock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Ramp {
+    start: f64,
+    from: f64,
+    end: f64,
+    to: f64,
+}
+
+impl Ramp {
+    fn constant(value: f64) -> Self {
+        Self {
+            start: 0.,
+            from: value,
+            end: 0.,
+            to: value,
+        }
+    }
+
+    fn at(&self, time: f64) -> f64 {
+        if time >= self.end || self.end <= self.start {
+            self.to
+        } else if time <= self.start {
+            self.from
+        } else {
+            self.from + (self.to - self.from) * (time - self.start) / (self.end - self.start)
+        }
+    }
+
+    /// Ramps from wherever this is at `start` to `to` at `end`.
+    fn towards(&self, start: f64, end: f64, to: f64) -> Self {
+        Self {
+            start,
+            from: self.at(start),
+            end,
+            to,
+        }
+    }
+}
+
+/// An effect scaling properties.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Layer {
+    Occlusion,
+    Propagation,
+    Doppler,
+}
+
+#[derive(Debug)]
+struct Scaled {
+    target: WeakHandle,
+    // What the application set, possibly mid fade.
+    base: Ramp,
+    factors: BTreeMap<Layer, Ramp>,
+}
+
+impl Scaled {
+    fn value_at(&self, time: f64) -> f64 {
+        self.factors
+            .values()
+            .fold(self.base.at(time), |v, factor| v * factor.at(time))
+    }
+
+    /// Whether anything is still moving after `now`.
+    fn ramping(&self, now: f64) -> bool {
+        once(&self.base)
+            .chain(self.factors.values())
+            .any(|v| v.end > now)
+    }
+
+    /// Forgets factors which no longer change anything, returning whether
+    /// only the application's value is left.
+    fn settle(&mut self, now: f64) -> bool {
+        self.factors.retain(|_, v| v.end > now || v.to != 1.);
+        self.factors.is_empty() && !self.ramping(now)
+    }
+
+    /// The automation replacing the property's from `now` on, moving in a
+    /// straight line between the ends of each ramp.
+    fn schedule(&self, now: f64) -> Plan {
+        let mut times = once(&self.base)
+            .chain(self.factors.values())
+            .flat_map(|v| [v.start, v.end])
+            .filter(|v| *v > now)
+            .collect::<Vec<_>>();
+        times.sort_by(f64::total_cmp);
+        times.dedup();
+        Plan {
+            clear: true,
+            points: once(now)
+                .chain(times)
+                .map(|v| (v, self.value_at(v)))
+                .collect(),
+            value: None,
+        }
+    }
+}
+
+/// What to do to a property once `SCALED` is unlocked.
+#[derive(Debug, Default)]
+struct Plan {
+    // Whether to drop the property's automation.
+    clear: bool,
+    // Jumps to the first point, then ramps through the rest.
+    points: Vec<(f64, f64)>,
+    // Set directly, after any automation changes.
+    value: Option<f64>,
+}
+
+impl Plan {
+    fn apply(self, target: &Handle, property: Property<f64>) -> Result<(), SynthizerError> {
+        if self.clear || !self.points.is_empty() {
+            let object = Target(target);
+            let mut batch = context_of(target)?.new_automation_batch()?;
+            if self.clear {
+                batch.clear(&object, property)?;
+            }
+            if let Some((time, value)) = self.points.first() {
+                batch.set(&object, property, *time, *value)?;
+            }
+            for (time, value) in self.points.iter().skip(1) {
+                batch.linear_ramp_to(&object, property, *time, *value)?;
+            }
+            batch.execute()?;
+        }
+        match self.value {
+            Some(v) => wrap!(unsafe { syz_setD(**target, property.id(), v) }),
+            None => Ok(()),
+        }
+    }
+}
+
+// Keyed by object and property. Entries for dropped objects are pruned
+// whenever one is added. Never held while calling into Synthizer, so changes
+// are planned under the lock and applied after.
+static SCALED: Mutex<BTreeMap<(syz_Handle, i32), Scaled>> = Mutex::new(BTreeMap::new());
+
+fn context_of(target: &Handle) -> Result<Context, SynthizerError> {
+    target
+        .context()
+        .map(Context)
+        .ok_or_else(|| SynthizerError::InvalidArgument("Object has no live context".to_string()))
+}
+
+// Reading the time reads a property itself, so this must be called without
+// `SCALED` locked.
+fn now(target: &Handle) -> Result<f64, SynthizerError> {
+    context_of(target)?.get_suggested_automation_time()
+}
+
+fn is_scaled(target: &Handle, property: i32) -> bool {
+    let scaled = SCALED.lock().unwrap();
+    scaled
+        .get(&(**target, property))
+        .is_some_and(|v| v.target.is(target))
+}
+
+/// The application's value of `property` on `target`, if it's being scaled.
+pub(crate) fn get(target: &Handle, property: i32) -> Result<Option<f64>, SynthizerError> {
+    if !is_scaled(target, property) {
+        return Ok(None);
+    }
+    let now = now(target)?;
+    let mut scaled = SCALED.lock().unwrap();
+    let key = (**target, property);
+    let entry = match scaled.get_mut(&key) {
+        Some(v) if v.target.is(target) => v,
+        _ => return Ok(None),
+    };
+    let value = entry.base.at(now);
+    if entry.settle(now) {
+        scaled.remove(&key);
+    }
+    Ok(Some(value))
+}
+
+/// Sets the application's value of `property` on `target`, returning
+/// whether it's being scaled. Properties which aren't should be set
+/// directly.
+///
+/// The value applies straight away. Only effects still ramping need
+/// automation, to carry on from it.
+pub(crate) fn set(target: &Handle, property: i32, value: f64) -> Result<bool, SynthizerError> {
+    if !is_scaled(target, property) {
+        return Ok(false);
+    }
+    let now = now(target)?;
+    let mut scaled = SCALED.lock().unwrap();
+    let key = (**target, property);
+    let entry = match scaled.get_mut(&key) {
+        Some(v) if v.target.is(target) => v,
+        _ => return Ok(false),
+    };
+    let was_ramping = entry.ramping(now);
+    entry.base = Ramp::constant(value);
+    let mut plan = if entry.ramping(now) {
+        entry.schedule(now)
+    } else {
+        Plan {
+            clear: was_ramping,
+            ..Plan::default()
+        }
+    };
+    plan.value = Some(entry.value_at(now));
+    if entry.settle(now) {
+        scaled.remove(&key);
+    }
+    drop(scaled);
+    plan.apply(target, Property::new(property))?;
+    Ok(true)
+}
+
+/// Plans a change to the entry for `property` on `target` with `f`, adding
+/// an entry if needed, then applies it.
+fn update(
+    target: &Handle,
+    property: Property<f64>,
+    f: impl FnOnce(&mut Scaled) -> Plan,
+) -> Result<(), SynthizerError> {
+    let mut current = 0.;
+    wrap!(unsafe { syz_getD(&mut current, **target, property.id()) })?;
+    let mut scaled = SCALED.lock().unwrap();
+    let key = (**target, property.id());
+    if !scaled.get(&key).is_some_and(|v| v.target.is(target)) {
+        scaled.retain(|_, v| v.target.upgrade().is_some());
+        scaled.insert(
+            key,
+            Scaled {
+                target: target.downgrade(),
+                base: Ramp::constant(current),
+                factors: BTreeMap::new(),
+            },
+        );
+    }
+    let plan = f(scaled.get_mut(&key).expect("Just inserted"));
+    drop(scaled);
+    plan.apply(target, property)
+}
+
+/// Ramps the application's value of `property` on `target` from where it is
+/// at `start` to `to` at `end`.
+pub(crate) fn ramp(
+    target: &Handle,
+    property: Property<f64>,
+    start: f64,
+    end: f64,
+    to: f64,
+) -> Result<(), SynthizerError> {
+    update(target, property, |entry| {
+        entry.base = entry.base.towards(start, end, to);
+        entry.schedule(start)
+    })
+}
+
+/// Ramps `layer`'s factor on `property` to `factor` over `duration` seconds.
+pub(crate) fn set_factor(
+    target: &Handle,
+    property: Property<f64>,
+    layer: Layer,
+    factor: f64,
+    duration: f64,
+) -> Result<(), SynthizerError> {
+    let now = now(target)?;
+    update(target, property, |entry| {
+        let ramp = entry
+            .factors
+            .get(&layer)
+            .copied()
+            .unwrap_or_else(|| Ramp::constant(1.))
+            .towards(now, now + duration, factor);
+        entry.factors.insert(layer, ramp);
+        entry.schedule(now)
+    })
+}
+
+/// Stops `layer` scaling `property`, leaving it at the application's value.
+pub(crate) fn remove_factor(
+    target: &Handle,
+    property: Property<f64>,
+    layer: Layer,
+) -> Result<(), SynthizerError> {
+    let now = now(target)?;
+    let mut scaled = SCALED.lock().unwrap();
+    let key = (**target, property.id());
+    let entry = match scaled.get_mut(&key) {
+        Some(v) if v.target.is(target) => v,
+        _ => return Ok(()),
+    };
+    if entry.factors.remove(&layer).is_none() {
+        return Ok(());
+    }
+    let plan = if entry.settle(now) {
+        // Nothing is scaling or ramping it any more, so it goes back to being
+        // set directly.
+        let value = entry.base.to;
+        scaled.remove(&key);
+        Plan {
+            clear: true,
+            value: Some(value),
+            ..Plan::default()
+        }
+    } else {
+        entry.schedule(now)
+    };
+    drop(scaled);
+    plan.apply(target, property)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{mock, property, Generator, Object, Source, Synthizer};
+
+    fn raw(target: &Handle, property: Property<f64>) -> f64 {
+        let mut out = 0.;
+        unsafe { syz_getD(&mut out, **target, property.id()) };
+        out
+    }
+
+    #[test]
+    fn sets_apply_immediately() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_direct_source()?;
+        set_factor(source.handle(), property::GAIN, Layer::Occlusion, 0.5, 0.)?;
+        let points = mock::automation(**source.handle(), property::GAIN.id());
+        source.set_gain(0.8)?;
+        assert_eq!(raw(source.handle(), property::GAIN), 0.4);
+        assert_eq!(source.get_gain()?, 0.8);
+        // Nothing was moving, so no automation was needed.
+        assert_eq!(
+            mock::automation(**source.handle(), property::GAIN.id()),
+            points
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sets_carry_ramping_factors_on() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_context()?;
+        let source = context.new_direct_source()?;
+        set_factor(source.handle(), property::GAIN, Layer::Occlusion, 0.5, 1.)?;
+        source.set_gain(0.8)?;
+        // Applied at once, then ramping on from there.
+        assert_eq!(raw(source.handle(), property::GAIN), 0.8);
+        let points = mock::automation(**source.handle(), property::GAIN.id());
+        assert_eq!(points.len(), 2);
+        assert_eq!((points[0].2[0], points[1].2[0]), (0.8, 0.4));
+        Ok(())
+    }
+
+    #[test]
+    fn forgets_properties_left_with_only_the_base() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let mut context = synthizer.new_headless_context()?;
+        let generator = context.new_buffer_generator()?;
+        let handle = generator.handle();
+        generator.set_pitch_bend(1.5)?;
+        set_factor(handle, property::PITCH_BEND, Layer::Doppler, 1., 0.)?;
+        assert!(is_scaled(handle, property::PITCH_BEND.id()));
+        assert_eq!(generator.get_pitch_bend()?, 1.5);
+        assert!(!is_scaled(handle, property::PITCH_BEND.id()));
+        let fade = generator.fade_to(0.5, Duration::from_millis(10))?;
+        assert!(is_scaled(handle, property::GAIN.id()));
+        while context.get_suggested_automation_time()? <= fade.end_time() {
+            context.render_frames(512)?;
+        }
+        handle.set(property::GAIN, 0.25)?;
+        assert!(!is_scaled(handle, property::GAIN.id()));
+        assert_eq!(raw(handle, property::GAIN), 0.25);
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use std::{ptr::null_mut, time::Duration};
+
+use enum_primitive_derive::Primitive;
+use num_traits::ToPrimitive;
+
+use crate::{
+    fade, ffi::*, occlusion, property, BiquadConfig, Context, Fade, Generator, Handle, Object,
+    SynthizerError,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Primitive)]
+#[repr(i32)]
+pub enum PannerStrategy {
+    Delegate = SYZ_PANNER_STRATEGY_SYZ_PANNER_STRATEGY_DELEGATE as i32,
+    HRTF = SYZ_PANNER_STRATEGY_SYZ_PANNER_STRATEGY_HRTF as i32,
+    Stereo = SYZ_PANNER_STRATEGY_SYZ_PANNER_STRATEGY_STEREO as i32,
+    Count = SYZ_PANNER_STRATEGY_SYZ_PANNER_STRATEGY_COUNT as i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Primitive)]
+#[repr(i32)]
+pub enum DistanceModel {
+    None = SYZ_DISTANCE_MODEL_SYZ_DISTANCE_MODEL_NONE as i32,
+    Linear = SYZ_DISTANCE_MODEL_SYZ_DISTANCE_MODEL_LINEAR as i32,
+    Exponential = SYZ_DISTANCE_MODEL_SYZ_DISTANCE_MODEL_EXPONENTIAL as i32,
+    Inverse = SYZ_DISTANCE_MODEL_SYZ_DISTANCE_MODEL_INVERSE as i32,
+    Count = SYZ_DISTANCE_MODEL_SYZ_DISTANCE_MODEL_COUNT as i32,
+}
+
+pub trait Source: Object {
+    fn add_generator(&self, generator: &impl Generator) -> Result<(), SynthizerError> {
+        wrap!(unsafe { syz_sourceAddGenerator(**self.handle(), **generator.handle()) })
+    }
+
+    fn remove_generator(&self, generator: &impl Generator) -> Result<(), SynthizerError> {
+        wrap!(unsafe { syz_sourceRemoveGenerator(**self.handle(), **generator.handle()) })
+    }
+
+    /// Pauses the source, and with it every generator it plays.
+    fn pause(&self) -> Result<(), SynthizerError> {
+        self.handle().pause()
+    }
+
+    /// Resumes a paused source.
+    fn play(&self) -> Result<(), SynthizerError> {
+        self.handle().play()
+    }
+
+    /// Whether the source is playing rather than paused.
+    fn is_playing(&self) -> bool {
+        self.handle().is_playing()
+    }
+
+    /// Ramps the gain to `gain` over `duration`, replacing any fade in
+    /// progress.
+    fn fade_to(&self, gain: f64, duration: Duration) -> Result<Fade, SynthizerError> {
+        fade::fade_to(self.handle(), gain, duration)
+    }
+
+    /// Fades the gain to 0 over `duration`, then pauses once the context's
+    /// clock passes the fade's end. Fading again first cancels the pause.
+    ///
+    /// The pause comes from a background thread and doesn't need events, so
+    /// it happens even if the context's events are never enabled or drained.
+    /// Headless contexts only pause once rendered past the fade.
+    fn fade_out_and_pause(&self, duration: Duration) -> Result<Fade, SynthizerError> {
+        fade::fade_out_and_pause(self.handle(), duration)
+    }
+
+    property!(gain: f64 = property::GAIN);
+    property!(
+        /// Filters everything the source plays.
+        filter: BiquadConfig = property::FILTER
+    );
+    property!(
+        /// Filters only the sound going straight to the listener.
+        filter_direct: BiquadConfig = property::FILTER_DIRECT
+    );
+    property!(
+        /// Filters only the sound sent to effects.
+        filter_effects: BiquadConfig = property::FILTER_EFFECTS
+    );
+}
+
+#[derive(Clone, Debug)]
+pub struct DirectSource(pub(crate) Handle);
+
+impl DirectSource {
+    pub(crate) fn new(context: &Context) -> Result<Self, SynthizerError> {
+        let mut handle = 0;
+        wrap!(
+            unsafe { syz_createDirectSource(&mut handle, **context, null_mut(), null_mut(), None) },
+            Self(Handle::new_in(handle, context.handle()))
+        )
+    }
+}
+
+make_object!(DirectSource);
+
+impl Source for DirectSource {}
+
+#[derive(Clone, Debug)]
+pub struct AngularPannedSource(pub(crate) Handle);
+
+impl AngularPannedSource {
+    pub(crate) fn new(
+        context: &Context,
+        panner_strategy: PannerStrategy,
+        azimuth: f64,
+        elevation: f64,
+    ) -> Result<Self, SynthizerError> {
+        let mut handle = 0;
+        wrap!(
+            unsafe {
+                syz_createAngularPannedSource(
+                    &mut handle,
+                    **context,
+                    panner_strategy.to_i32().unwrap(),
+                    azimuth,
+                    elevation,
+                    null_mut(),
+                    null_mut(),
+                    None,
+                )
+            },
+            Self(Handle::new_in(handle, context.handle()))
+        )
+    }
+
+    property!(pub azimuth: f64 = property::AZIMUTH);
+    property!(pub elevation: f64 = property::ELEVATION);
+}
+
+make_object!(AngularPannedSource);
+
+impl Source for AngularPannedSource {}
+
+#[derive(Clone, Debug)]
+pub struct ScalarPannedSource(pub(crate) Handle);
+
+impl ScalarPannedSource {
+    pub(crate) fn new(
+        context: &Context,
+        panner_strategy: PannerStrategy,
+        panning_scalar: f64,
+    ) -> Result<Self, SynthizerError> {
+        let mut handle = 0;
+        wrap!(
+            unsafe {
+                syz_createScalarPannedSource(
+                    &mut handle,
+                    **context,
+                    panner_strategy.to_i32().unwrap(),
+                    panning_scalar,
+                    null_mut(),
+                    null_mut(),
+                    None,
+                )
+            },
+            Self(Handle::new_in(handle, context.handle()))
+        )
+    }
+
+    property!(pub panning_scalar: f64 = property::PANNING_SCALAR);
+}
+
+make_object!(ScalarPannedSource);
+
+impl Source for ScalarPannedSource {}
+
+#[derive(Clone, Debug)]
+pub struct Source3D(pub(crate) Handle);
+
+impl Source3D {
+    pub(crate) fn new(
+        context: &Context,
+        panner_strategy: PannerStrategy,
+        x: f64,
+        y: f64,
+        z: f64,
+    ) -> Result<Self, SynthizerError> {
+        let mut handle = 0;
+        let source = wrap!(
+            unsafe {
+                syz_createSource3D(
+                    &mut handle,
+                    **context,
+                    panner_strategy.to_i32().unwrap(),
+                    x,
+                    y,
+                    z,
+                    null_mut(),
+                    null_mut(),
+                    None,
+                )
+            },
+            Self(Handle::new_in(handle, context.handle()))
+        )?;
+        occlusion::track(context.handle(), source.handle());
+        Ok(source)
+    }
+
+    d3!(pub position = property::POSITION);
+    d6!(pub orientation = property::ORIENTATION);
+    property!(pub distance_model: DistanceModel = property::DISTANCE_MODEL);
+    property!(pub distance_ref: f64 = property::DISTANCE_REF);
+    property!(pub distance_max: f64 = property::DISTANCE_MAX);
+    property!(pub rolloff: f64 = property::ROLLOFF);
+    property!(pub closeness_boost: f64 = property::CLOSENESS_BOOST);
+    property!(pub closeness_boost_distance: f64 = property::CLOSENESS_BOOST_DISTANCE);
+}
+
+make_object!(Source3D);
+
+impl Source for Source3D {}
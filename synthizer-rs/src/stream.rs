@@ -0,0 +1,266 @@
+//! Custom stream protocols, for reading audio from anywhere Rust can.
+//!
+//! A protocol is registered under a name with
+//! [`Synthizer::register_stream_protocol`](crate::Synthizer::register_stream_protocol),
+//! then used by passing [`Protocol::Custom`](crate::Protocol::Custom) to
+//! anything that reads a stream, with a path the protocol understands.
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    io::{self, Read, Seek, SeekFrom},
+    os::raw::{c_char, c_int, c_ulonglong, c_void},
+    panic::{catch_unwind, AssertUnwindSafe},
+    slice,
+};
+
+use crate::{ffi::*, SynthizerError};
+
+/// A stream of bytes for Synthizer to decode.
+///
+/// This is implemented for everything that is `Read + Seek`. Implement it
+/// directly for streams which can't seek, or which need to do something when
+/// closed.
+pub trait StreamSource: Send {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Seeks to an absolute position. Only called if `length` is known.
+    fn seek(&mut self, _pos: u64) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// The length of the stream in bytes, or `None` if it can't seek.
+    fn length(&mut self) -> Option<u64> {
+        None
+    }
+
+    /// Called once Synthizer is done with the stream.
+    fn close(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: Read + Seek + Send> StreamSource for T {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Read::read(self, buf)
+    }
+
+    fn seek(&mut self, pos: u64) -> io::Result<()> {
+        Seek::seek(self, SeekFrom::Start(pos))?;
+        Ok(())
+    }
+
+    fn length(&mut self) -> Option<u64> {
+        let position = self.stream_position().ok()?;
+        let length = Seek::seek(self, SeekFrom::End(0)).ok()?;
+        Seek::seek(self, SeekFrom::Start(position)).ok()?;
+        Some(length)
+    }
+}
+
+type OpenCallback = dyn Fn(&str) -> io::Result<Box<dyn StreamSource>> + Send + Sync;
+
+thread_local! {
+    // Synthizer copies error messages as soon as a callback returns, so one
+    // per thread is enough to keep them alive.
+    static ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Runs a callback, reporting errors and panics to Synthizer.
+fn callback(err_msg: *mut *const c_char, f: impl FnOnce() -> io::Result<()>) -> c_int {
+    let message = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return 0,
+        Ok(Err(e)) => e.to_string(),
+        Err(_) => "Stream callback panicked".to_string(),
+    };
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    ERROR.with(|v| {
+        let mut v = v.borrow_mut();
+        *v = message;
+        if !err_msg.is_null() {
+            unsafe { *err_msg = v.as_ptr() };
+        }
+    });
+    1
+}
+
+unsafe fn source<'a>(userdata: *mut c_void) -> &'a mut Box<dyn StreamSource> {
+    &mut *(userdata as *mut Box<dyn StreamSource>)
+}
+
+unsafe extern "C" fn read_cb(
+    read: *mut c_ulonglong,
+    requested: c_ulonglong,
+    destination: *mut c_char,
+    userdata: *mut c_void,
+    err_msg: *mut *const c_char,
+) -> c_int {
+    callback(err_msg, || {
+        let source = source(userdata);
+        let buf = slice::from_raw_parts_mut(destination as *mut u8, requested as usize);
+        // Synthizer treats a short read as the end of the stream.
+        let mut total = 0;
+        while total < buf.len() {
+            match source.read(&mut buf[total..]) {
+                Ok(0) => break,
+                Ok(n) => total += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        *read = total as c_ulonglong;
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn seek_cb(
+    pos: c_ulonglong,
+    userdata: *mut c_void,
+    err_msg: *mut *const c_char,
+) -> c_int {
+    callback(err_msg, || source(userdata).seek(pos))
+}
+
+unsafe extern "C" fn close_cb(userdata: *mut c_void, err_msg: *mut *const c_char) -> c_int {
+    callback(err_msg, || source(userdata).close())
+}
+
+unsafe extern "C" fn destroy_cb(userdata: *mut c_void) {
+    drop(Box::from_raw(userdata as *mut Box<dyn StreamSource>));
+}
+
+unsafe extern "C" fn open_cb(
+    callbacks: *mut syz_CustomStreamDef,
+    _protocol: *const c_char,
+    path: *const c_char,
+    _param: *mut c_void,
+    userdata: *mut c_void,
+    err_msg: *mut *const c_char,
+) -> c_int {
+    callback(err_msg, || {
+        let open = &*(userdata as *const Box<OpenCallback>);
+        let path = CStr::from_ptr(path).to_string_lossy();
+        fill_callbacks(&mut *callbacks, open(&path)?);
+        Ok(())
+    })
+}
+
+/// Points `callbacks` at `source`, which is then owned by Synthizer.
+fn fill_callbacks(callbacks: &mut syz_CustomStreamDef, mut source: Box<dyn StreamSource>) {
+    let length = source.length();
+    callbacks.read_cb = Some(read_cb);
+    callbacks.seek_cb = length.map(|_| seek_cb as _);
+    // Synthizer only calls the destroy callback if there's a close callback
+    // too.
+    callbacks.close_cb = Some(close_cb);
+    callbacks.destroy_cb = Some(destroy_cb);
+    callbacks.length = length.map(|v| v as i64).unwrap_or(-1);
+    callbacks.userdata = Box::into_raw(Box::new(source)) as *mut c_void;
+}
+
+pub(crate) fn register_stream_protocol<F>(protocol: &str, open: F) -> Result<(), SynthizerError>
+where
+    F: Fn(&str) -> io::Result<Box<dyn StreamSource>> + Send + Sync + 'static,
+{
+    let protocol = CString::new(protocol)?;
+    let open: Box<OpenCallback> = Box::new(open);
+    let userdata = Box::into_raw(Box::new(open));
+    let result = wrap!(unsafe {
+        syz_registerStreamProtocol(protocol.as_ptr(), Some(open_cb), userdata as *mut c_void)
+    });
+    // Synthizer can't unregister protocols, so on success the callback lives
+    // for the rest of the process.
+    if result.is_err() {
+        drop(unsafe { Box::from_raw(userdata) });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    use super::*;
+    use crate::{mock, Buffer, Protocol, Synthizer};
+
+    fn load(protocol: &str, path: &str) -> Result<Buffer, SynthizerError> {
+        Buffer::new(Protocol::Custom(protocol.into()), path.as_ref(), "")
+    }
+
+    #[test]
+    fn reads_from_read_seek() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        synthizer.register_stream_protocol("test-cursor", |path| match path {
+            "sound.wav" => Ok(Box::new(Cursor::new(mock::WAV))),
+            "noise.wav" => Ok(Box::new(Cursor::new(b"not audio"))),
+            _ => Err(io::ErrorKind::NotFound.into()),
+        })?;
+        load("test-cursor", "sound.wav")?;
+        let error = load("test-cursor", "noise.wav").unwrap_err();
+        assert!(matches!(error, SynthizerError::Decoding(_)));
+        let error = load("test-cursor", "missing.wav").unwrap_err();
+        assert!(matches!(error, SynthizerError::Io(_)));
+        Ok(())
+    }
+
+    struct Unseekable {
+        data: &'static [u8],
+        closed: Arc<AtomicBool>,
+    }
+
+    impl StreamSource for Unseekable {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            Read::read(&mut self.data, buf)
+        }
+
+        fn close(&mut self) -> io::Result<()> {
+            self.closed.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn closes_unseekable_streams() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let closed = Arc::new(AtomicBool::new(false));
+        let flag = closed.clone();
+        synthizer.register_stream_protocol("test-unseekable", move |_| {
+            Ok(Box::new(Unseekable {
+                data: mock::WAV,
+                closed: flag.clone(),
+            }))
+        })?;
+        load("test-unseekable", "sound.wav")?;
+        assert!(closed.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[test]
+    fn panics_become_errors() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        synthizer.register_stream_protocol("test-panic", |_| panic!("oops"))?;
+        let error = load("test-panic", "sound.wav").unwrap_err();
+        assert!(error.message().unwrap().contains("panicked"));
+        Ok(())
+    }
+
+    #[test]
+    fn protocols_register_once() -> Result<(), SynthizerError> {
+        let synthizer = Synthizer::new()?;
+        let open =
+            |_: &str| -> io::Result<Box<dyn StreamSource>> { Ok(Box::new(Cursor::new(mock::WAV))) };
+        synthizer.register_stream_protocol("test-once", open)?;
+        assert!(synthizer
+            .register_stream_protocol("test-once", open)
+            .is_err());
+        let error = load("test-unregistered", "sound.wav").unwrap_err();
+        assert!(matches!(error, SynthizerError::Io(_)));
+        Ok(())
+    }
+}
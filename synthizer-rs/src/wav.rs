@@ -0,0 +1,152 @@
+use std::{
+    convert::TryFrom,
+    io::{self, Write},
+    sync::Arc,
+};
+
+use crate::SynthizerError;
+
+/// The sample format of a rendered WAV file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WavFormat {
+    /// 16-bit PCM, which everything can play.
+    Int16,
+    /// 32-bit float, which keeps everything Synthizer rendered, including
+    /// samples beyond full scale.
+    Float32,
+}
+
+impl WavFormat {
+    fn bytes_per_sample(self) -> u32 {
+        match self {
+            WavFormat::Int16 => 2,
+            WavFormat::Float32 => 4,
+        }
+    }
+}
+
+/// The size of one frame, which the header stores in 16 bits.
+fn block_align(format: WavFormat, channels: u32) -> Result<u16, SynthizerError> {
+    channels
+        .checked_mul(format.bytes_per_sample())
+        .and_then(|v| u16::try_from(v).ok())
+        .filter(|v| *v > 0)
+        .ok_or_else(|| {
+            SynthizerError::InvalidArgument(format!("A WAV file can't have {} channels", channels))
+        })
+}
+
+/// The most frames a WAV file with these channels can hold.
+pub(crate) fn max_frames(format: WavFormat, channels: u32) -> Result<u32, SynthizerError> {
+    Ok((u32::MAX - 36) / block_align(format, channels)? as u32)
+}
+
+/// Writes the header of a WAV file holding `frames` frames.
+pub(crate) fn write_header(
+    out: &mut impl Write,
+    format: WavFormat,
+    sample_rate: u32,
+    channels: u32,
+    frames: u32,
+) -> Result<(), SynthizerError> {
+    let block_align = block_align(format, channels)?;
+    let data_len = frames
+        .checked_mul(block_align as u32)
+        .filter(|v| *v <= u32::MAX - 36)
+        .ok_or_else(|| SynthizerError::InvalidArgument("Too long for a WAV file".to_string()))?;
+    let bytes_per_sample = format.bytes_per_sample();
+    let tag: u16 = match format {
+        WavFormat::Int16 => 1,
+        WavFormat::Float32 => 3,
+    };
+    let mut write = || -> io::Result<()> {
+        out.write_all(b"RIFF")?;
+        out.write_all(&(36 + data_len).to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&tag.to_le_bytes())?;
+        // Fits, since the block align does.
+        out.write_all(&(channels as u16).to_le_bytes())?;
+        out.write_all(&sample_rate.to_le_bytes())?;
+        out.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        out.write_all(&block_align.to_le_bytes())?;
+        out.write_all(&((bytes_per_sample * 8) as u16).to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&data_len.to_le_bytes())
+    };
+    write().map_err(|e| SynthizerError::Output(Arc::new(e)))
+}
+
+pub(crate) fn write_samples(
+    out: &mut impl Write,
+    format: WavFormat,
+    samples: &[f32],
+) -> io::Result<()> {
+    for sample in samples {
+        match format {
+            WavFormat::Int16 => {
+                let sample = (sample.clamp(-1., 1.) * i16::MAX as f32).round() as i16;
+                out.write_all(&sample.to_le_bytes())?;
+            }
+            WavFormat::Float32 => out.write_all(&sample.to_le_bytes())?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_pcm() -> Result<(), SynthizerError> {
+        let mut out = vec![];
+        write_header(&mut out, WavFormat::Int16, 44100, 2, 2)?;
+        write_samples(&mut out, WavFormat::Int16, &[0., 1., -1., 2.]).unwrap();
+        assert_eq!(out.len(), 44 + 8);
+        assert_eq!(&out[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes([out[4], out[5], out[6], out[7]]), 44);
+        assert_eq!(u16::from_le_bytes([out[20], out[21]]), 1);
+        assert_eq!(u32::from_le_bytes([out[40], out[41], out[42], out[43]]), 8);
+        let samples: Vec<i16> = out[44..]
+            .chunks(2)
+            .map(|v| i16::from_le_bytes([v[0], v[1]]))
+            .collect();
+        assert_eq!(samples, [0, i16::MAX, -i16::MAX, i16::MAX]);
+        Ok(())
+    }
+
+    #[test]
+    fn writes_float() -> Result<(), SynthizerError> {
+        let mut out = vec![];
+        write_header(&mut out, WavFormat::Float32, 44100, 2, 1)?;
+        write_samples(&mut out, WavFormat::Float32, &[0.5, 2.]).unwrap();
+        assert_eq!(out.len(), 44 + 8);
+        assert_eq!(u16::from_le_bytes([out[20], out[21]]), 3);
+        assert_eq!(u16::from_le_bytes([out[34], out[35]]), 32);
+        assert_eq!(&out[48..], &2f32.to_le_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn writes_any_channels() -> Result<(), SynthizerError> {
+        let mut out = vec![];
+        let frames = max_frames(WavFormat::Float32, 6)?;
+        write_header(&mut out, WavFormat::Float32, 44100, 6, frames)?;
+        assert_eq!(u16::from_le_bytes([out[22], out[23]]), 6);
+        assert_eq!(u16::from_le_bytes([out[32], out[33]]), 24);
+        assert!(write_header(&mut out, WavFormat::Float32, 44100, 6, frames + 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unrepresentable_channels() {
+        for channels in [0, u16::MAX as u32, u16::MAX as u32 + 1, u32::MAX] {
+            assert!(matches!(
+                write_header(&mut vec![], WavFormat::Int16, 44100, channels, 1),
+                Err(SynthizerError::InvalidArgument(_))
+            ));
+            assert!(max_frames(WavFormat::Float32, channels).is_err());
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::env;
 use std::io;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 fn main() -> Result<(), io::Error> {
@@ -9,9 +9,22 @@ fn main() -> Result<(), io::Error> {
     let executable = env::var("CMAKE").unwrap_or_else(|_| "cmake".to_owned());
     let mut cmd = Command::new(&executable);
     cmd.current_dir(&out_dir);
-    let mut lib_dir = env::current_dir()?;
-    lib_dir.push("synthizer");
-    cmd.arg(lib_dir);
+    // SYNTHIZER_DIR points at a Synthizer 0.11.13 checkout to use instead of
+    // the submodule.
+    println!("cargo:rerun-if-env-changed=SYNTHIZER_DIR");
+    let lib_dir = match env::var_os("SYNTHIZER_DIR") {
+        Some(v) => PathBuf::from(v),
+        None => env::current_dir()?.join("synthizer"),
+    };
+    let header = lib_dir.join("include").join("synthizer.h");
+    if !header.exists() {
+        eprintln!(
+            "Synthizer sources not found in {}. Run `git submodule update --init`, or set SYNTHIZER_DIR to a Synthizer 0.11.13 checkout.",
+            lib_dir.display()
+        );
+        std::process::exit(1);
+    }
+    cmd.arg(&lib_dir);
     cmd.args(&["-G", "Ninja"]);
     cmd.arg("-DCMAKE_BUILD_TYPE=Release");
     cmd.arg("-DSYNTHIZER_LIB_TYPE=SHARED");
@@ -36,8 +49,13 @@ fn main() -> Result<(), io::Error> {
     println!("cargo:rustc-link-search=native={}", &out_dir);
     println!("cargo:rustc-link-lib=static=synthizer");
     let _ = bindgen::builder()
-        .header("synthizer/include/synthizer.h")
-        .header("synthizer/include/synthizer_constants.h")
+        .header(header.to_string_lossy())
+        .header(
+            lib_dir
+                .join("include")
+                .join("synthizer_constants.h")
+                .to_string_lossy(),
+        )
         .generate()
         .unwrap()
         .write_to_file(Path::new(&out_dir).join("synthizer_sys.rs"));